@@ -0,0 +1,92 @@
+use std::time::{Duration, Instant};
+
+// (fps factor, size factor) relative to the configured video settings
+const LEVELS: &[(f32, f32)] = &[
+    (1.0, 1.0),
+    (0.66, 1.0),
+    (0.5, 0.75),
+    (0.33, 0.5),
+    (0.2, 0.5),
+];
+
+const QUEUE_HIGH: usize = 8;
+const QUEUE_LOW: usize = 1;
+const LATENCY_HIGH: Duration = Duration::from_millis(150);
+const LATENCY_LOW: Duration = Duration::from_millis(40);
+
+// Step down quickly, step back up only after the link has been healthy a while
+const STEP_DOWN_AFTER: Duration = Duration::from_secs(1);
+const STEP_UP_AFTER: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QualityLevel {
+    pub width: u16,
+    pub height: u16,
+    pub fps: u32,
+    pub degraded: bool,
+}
+
+/// Picks an effective frame size and rate from the outgoing queue depth and
+/// socket write latency.
+pub struct QualityController {
+    base: (u16, u16, u32),
+    level: usize,
+    last_change: Instant,
+    healthy_since: Option<Instant>,
+}
+
+impl QualityController {
+    pub fn new(width: u16, height: u16, fps: u32) -> Self {
+        Self {
+            base: (width, height, fps),
+            level: 0,
+            last_change: Instant::now(),
+            healthy_since: None,
+        }
+    }
+
+    /// Change the full-quality settings the levels are derived from.
+    pub fn set_base(&mut self, width: u16, height: u16, fps: u32) -> QualityLevel {
+        self.base = (width, height, fps);
+        self.current()
+    }
+
+    pub fn current(&self) -> QualityLevel {
+        let (width, height, fps) = self.base;
+        let (fps_factor, size_factor) = LEVELS[self.level];
+        QualityLevel {
+            width: ((width as f32 * size_factor) as u16).max(1),
+            height: ((height as f32 * size_factor) as u16).max(1),
+            fps: ((fps as f32 * fps_factor) as u32).max(1),
+            degraded: self.level > 0,
+        }
+    }
+
+    /// Returns the new level when it changed.
+    pub fn observe(&mut self, queued: usize, latency: Duration) -> Option<QualityLevel> {
+        let now = Instant::now();
+        let congested = queued > QUEUE_HIGH || latency > LATENCY_HIGH;
+        let healthy = queued <= QUEUE_LOW && latency < LATENCY_LOW;
+
+        if congested {
+            self.healthy_since = None;
+            if self.level + 1 < LEVELS.len() && now.duration_since(self.last_change) >= STEP_DOWN_AFTER {
+                self.level += 1;
+                self.last_change = now;
+                return Some(self.current());
+            }
+        } else if healthy {
+            let since = *self.healthy_since.get_or_insert(now);
+            if self.level > 0 && now.duration_since(since) >= STEP_UP_AFTER {
+                self.level -= 1;
+                self.last_change = now;
+                self.healthy_since = Some(now);
+                return Some(self.current());
+            }
+        } else {
+            self.healthy_since = None;
+        }
+
+        None
+    }
+}
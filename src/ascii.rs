@@ -2,6 +2,9 @@ use anyhow::Result;
 
 const PALETTE: &[u8] = b" .'`^\",:;Il!i><~+_-?][}{1)(|\\tfjrxnuvczXYUJCLQ0OZmwqpdbkhao*#MW&8%B@$";
 
+// Per-channel color change ignored by change_ratio (sensor noise)
+const COLOR_TOLERANCE: u8 = 24;
+
 #[derive(Clone, Debug)]
 pub struct AsciiFrame {
     pub width: u16,
@@ -45,6 +48,59 @@ impl AsciiFrame {
         Ok(Self { width, height, cells })
     }
 
+    pub fn put_str(&mut self, x: u16, y: u16, text: &str, color: (u8, u8, u8)) {
+        if y >= self.height {
+            return;
+        }
+        for (i, ch) in text.chars().enumerate() {
+            let cx = x as usize + i;
+            if cx >= self.width as usize {
+                break;
+            }
+            let idx = y as usize * self.width as usize + cx;
+            self.cells[idx] = (ch, color.0, color.1, color.2);
+        }
+    }
+
+    /// Nearest-neighbour resample to a new grid size.
+    pub fn resize(&self, width: u16, height: u16) -> Self {
+        let width = width.max(1);
+        let height = height.max(1);
+        let mut cells = Vec::with_capacity(width as usize * height as usize);
+
+        for y in 0..height {
+            let sy = (y as usize * self.height as usize) / height as usize;
+            for x in 0..width {
+                let sx = (x as usize * self.width as usize) / width as usize;
+                let idx = sy * self.width as usize + sx;
+                cells.push(self.cells.get(idx).copied().unwrap_or((' ', 0, 0, 0)));
+            }
+        }
+
+        Self { width, height, cells }
+    }
+
+    /// Fraction of cells (0.0..=1.0) that differ noticeably from `previous`.
+    pub fn change_ratio(&self, previous: &AsciiFrame) -> f32 {
+        if self.width != previous.width || self.height != previous.height || self.cells.is_empty() {
+            return 1.0;
+        }
+
+        let changed = self
+            .cells
+            .iter()
+            .zip(&previous.cells)
+            .filter(|(a, b)| {
+                a.0 != b.0
+                    || a.1.abs_diff(b.1) > COLOR_TOLERANCE
+                    || a.2.abs_diff(b.2) > COLOR_TOLERANCE
+                    || a.3.abs_diff(b.3) > COLOR_TOLERANCE
+            })
+            .count();
+
+        changed as f32 / self.cells.len() as f32
+    }
+
     #[allow(dead_code)]
     pub fn to_string_colored(&self) -> String {
         let mut result = String::new();
@@ -80,7 +136,8 @@ impl AsciiFrame {
         
         let width = u16::from_le_bytes([data[0], data[1]]);
         let height = u16::from_le_bytes([data[2], data[3]]);
-        let expected_len = 4 + (width * height * 4) as usize;
+        // Sizes come from a peer, so this mustn't overflow
+        let expected_len = 4 + width as usize * height as usize * 4;
         
         if data.len() != expected_len {
             anyhow::bail!("Invalid frame data length");
@@ -1,84 +1,272 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use futures_util::{SinkExt, StreamExt};
+use std::sync::{
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
 use tokio::sync::mpsc;
-use tokio_tungstenite::{connect_async, tungstenite::Message as WsMessage};
+use tokio_tungstenite::{connect_async, tungstenite::Message as WsMessage, MaybeTlsStream, WebSocketStream};
+use uuid::Uuid;
 
-use crate::protocol::Message;
+use crate::protocol::{Message, Sequenced};
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+// Reconnect delay doubles from the first value up to the cap
+const RECONNECT_MIN: Duration = Duration::from_secs(1);
+const RECONNECT_MAX: Duration = Duration::from_secs(30);
+// The server pings every few seconds; this long without hearing from it means the link is dead
+const SERVER_TIMEOUT: Duration = Duration::from_secs(15);
+// How long quitting waits for the Close frame to go out
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConnectionStatus {
+    Connected,
+    /// Lost the connection; the next attempt is at `retry_at`
+    Reconnecting { attempt: u32, retry_at: Instant },
+    /// Not connected and not trying
+    #[default]
+    Offline,
+}
 
 pub struct ChatClient {
     pub tx: mpsc::UnboundedSender<Message>,
     pub rx: mpsc::UnboundedReceiver<Message>,
+    queued: Arc<AtomicUsize>,
+    send_latency_us: Arc<AtomicU64>,
+    status: Arc<Mutex<ConnectionStatus>>,
+    link: tokio::task::JoinHandle<()>,
+}
+
+/// One end of the app's channels, carried over from one socket to the next.
+struct Link {
+    url: String,
+    rx_from_app: mpsc::UnboundedReceiver<Message>,
+    tx_to_app: mpsc::UnboundedSender<Message>,
+    queued: Arc<AtomicUsize>,
+    send_latency_us: Arc<AtomicU64>,
+    status: Arc<Mutex<ConnectionStatus>>,
+    /// Who we joined as and the server's resume token, to rejoin after a reconnect
+    username: Option<String>,
+    identity: Option<Uuid>,
+    token: Option<String>,
+    /// Position in the server's history of the last replayable message received
+    last_seq: Option<u64>,
 }
 
 impl ChatClient {
+    /// Connects to `url`; if the connection later drops it is re-established in the background.
     pub async fn connect(url: &str) -> Result<Self> {
         let (ws_stream, _) = connect_async(url)
             .await
             .context("Failed to connect to server")?;
+        Ok(Self::start(url, Some(ws_stream), None))
+    }
+    
+    /// For when the server can't be reached yet: keeps trying in the background
+    /// and joins as `username` once it gets through.
+    pub fn connect_later(url: &str, username: String, identity: Uuid) -> Self {
+        Self::start(url, None, Some((username, identity)))
+    }
+    
+    fn start(url: &str, ws_stream: Option<WsStream>, join: Option<(String, Uuid)>) -> Self {
+        let (tx_to_ws, rx_from_app) = mpsc::unbounded_channel::<Message>();
+        let (tx_to_app, rx_from_ws) = mpsc::unbounded_channel::<Message>();
         
-        let (write, read) = ws_stream.split();
+        let queued = Arc::new(AtomicUsize::new(0));
+        let send_latency_us = Arc::new(AtomicU64::new(0));
+        let status = Arc::new(Mutex::new(ConnectionStatus::Offline));
         
-        let (tx_to_ws, mut rx_from_app) = mpsc::unbounded_channel::<Message>();
-        let (tx_to_app, rx_from_ws) = mpsc::unbounded_channel::<Message>();
+        let link = Link {
+            url: url.to_string(),
+            rx_from_app,
+            tx_to_app,
+            queued: queued.clone(),
+            send_latency_us: send_latency_us.clone(),
+            status: status.clone(),
+            username: join.as_ref().map(|(username, _)| username.clone()),
+            identity: join.map(|(_, identity)| identity),
+            token: None,
+            last_seq: None,
+        };
+        if ws_stream.is_some() {
+            link.set_status(ConnectionStatus::Connected);
+        }
+        let link = tokio::spawn(link.supervise(ws_stream));
         
-        // Handle sending messages to WebSocket
-        let tx_to_app_clone = tx_to_app.clone();
-        tokio::spawn(async move {
-            let mut write = write;
-            while let Some(msg) = rx_from_app.recv().await {
-                if let Ok(json) = serde_json::to_string(&msg) {
-                    if write.send(WsMessage::Text(json)).await.is_err() {
-                        let _ = tx_to_app_clone.send(Message::Error {
-                            message: "Connection lost".to_string(),
-                        });
-                        break;
-                    }
+        Self {
+            tx: tx_to_ws,
+            rx: rx_from_ws,
+            queued,
+            send_latency_us,
+            status,
+            link,
+        }
+    }
+    
+    /// Hangs up cleanly, so the server tells everyone we left rather than waiting for us to resume.
+    pub async fn close(self) {
+        let Self { tx, link, .. } = self;
+        drop(tx);
+        let _ = tokio::time::timeout(CLOSE_TIMEOUT, link).await;
+    }
+    
+    /// Fails while the connection is down, rather than queueing for a socket that may never return.
+    pub async fn send(&self, message: Message) -> Result<()> {
+        if self.status() != ConnectionStatus::Connected {
+            bail!("Not connected");
+        }
+        // Count before handing off, so the writer never decrements below zero
+        self.queued.fetch_add(1, Ordering::Relaxed);
+        self.tx.send(message).map_err(|e| {
+            self.queued.fetch_sub(1, Ordering::Relaxed);
+            anyhow::anyhow!("Failed to send message: {}", e)
+        })
+    }
+    
+    /// Messages handed to `send` that haven't been written to the socket yet.
+    pub fn queue_depth(&self) -> usize {
+        self.queued.load(Ordering::Relaxed)
+    }
+    
+    /// Smoothed time a single socket write takes.
+    pub fn send_latency(&self) -> Duration {
+        Duration::from_micros(self.send_latency_us.load(Ordering::Relaxed))
+    }
+    
+    pub fn status(&self) -> ConnectionStatus {
+        *self.status.lock().unwrap()
+    }
+    
+    #[allow(dead_code)]
+    pub async fn recv(&mut self) -> Option<Message> {
+        self.rx.recv().await
+    }
+}
+
+impl Link {
+    /// Runs sockets one after another, backing off between failed attempts, until the app goes away.
+    async fn supervise(mut self, first: Option<WsStream>) {
+        let mut stream = first;
+        let mut attempt = 0;
+        loop {
+            if let Some(ws) = stream.take() {
+                self.set_status(ConnectionStatus::Connected);
+                attempt = 0;
+                if !self.run(ws).await {
+                    break;
                 }
             }
-        });
+            
+            attempt += 1;
+            let delay = (RECONNECT_MIN * 2u32.pow((attempt - 1).min(5))).min(RECONNECT_MAX);
+            self.set_status(ConnectionStatus::Reconnecting {
+                attempt,
+                retry_at: Instant::now() + delay,
+            });
+            self.discard_queued();
+            tokio::time::sleep(delay).await;
+            if self.tx_to_app.is_closed() {
+                break;
+            }
+            stream = connect_async(&self.url).await.ok().map(|(ws, _)| ws);
+        }
+        self.set_status(ConnectionStatus::Offline);
+    }
+    
+    /// Pumps messages both ways until the socket fails. Returns false once the app has gone away.
+    async fn run(&mut self, ws: WsStream) -> bool {
+        let (mut write, mut read) = ws.split();
         
-        // Handle receiving messages from WebSocket
-        tokio::spawn(async move {
-            let mut read = read;
-            while let Some(msg) = read.next().await {
-                match msg {
-                    Ok(WsMessage::Text(text)) => {
-                        if let Ok(message) = Message::from_bytes(text.as_bytes()) {
-                            if tx_to_app.send(message).is_err() {
-                                break;
-                            }
-                        }
+        // After a reconnect, pick the session back up before anything else goes out
+        let rejoin = match (&self.token, &self.username) {
+            (Some(token), Some(username)) => Some(Message::Resume {
+                token: token.clone(),
+                username: username.clone(),
+                identity: self.identity,
+                last_seq: self.last_seq,
+            }),
+            (None, Some(username)) => Some(Message::Join {
+                id: Uuid::nil(),
+                username: username.clone(),
+                identity: self.identity,
+            }),
+            _ => None,
+        };
+        if let Some(json) = rejoin.and_then(|m| serde_json::to_string(&m).ok()) {
+            if write.send(WsMessage::Text(json)).await.is_err() {
+                return true;
+            }
+        }
+        
+        let mut deadline = tokio::time::Instant::now() + SERVER_TIMEOUT;
+        loop {
+            tokio::select! {
+                msg = self.rx_from_app.recv() => {
+                    let Some(msg) = msg else {
+                        let _ = write.send(WsMessage::Close(None)).await;
+                        return false;
+                    };
+                    self.queued.fetch_sub(1, Ordering::Relaxed);
+                    if let Message::Join { username, identity, .. } = &msg {
+                        self.username = Some(username.clone());
+                        self.identity = *identity;
                     }
-                    Ok(WsMessage::Close(_)) => {
-                        let _ = tx_to_app.send(Message::Error {
-                            message: "Server closed connection".to_string(),
-                        });
-                        break;
+                    if let Ok(json) = serde_json::to_string(&msg) {
+                        let started = Instant::now();
+                        if write.send(WsMessage::Text(json)).await.is_err() {
+                            return true;
+                        }
+                        
+                        // Smoothed so a single slow write doesn't look like congestion
+                        let sample = started.elapsed().as_micros() as u64;
+                        let previous = self.send_latency_us.load(Ordering::Relaxed);
+                        self.send_latency_us.store((previous * 7 + sample) / 8, Ordering::Relaxed);
                     }
-                    Err(e) => {
-                        let _ = tx_to_app.send(Message::Error {
-                            message: format!("WebSocket error: {}", e),
-                        });
-                        break;
+                }
+                frame = read.next() => {
+                    deadline = tokio::time::Instant::now() + SERVER_TIMEOUT;
+                    match frame {
+                        Some(Ok(WsMessage::Text(text))) => {
+                            if let Ok(message) = Message::from_bytes(text.as_bytes()) {
+                                if let Message::Session { token, resumed, .. } = &message {
+                                    self.token = Some(token.clone());
+                                    // A new session's history position comes with its first messages
+                                    if !resumed {
+                                        self.last_seq = None;
+                                    }
+                                }
+                                // Only these carry a seq; parsed again rather than slowing down video
+                                if message.replayable() {
+                                    if let Ok(Sequenced { seq: Some(seq), .. }) = serde_json::from_str(&text) {
+                                        self.last_seq = self.last_seq.max(Some(seq));
+                                    }
+                                }
+                                if self.tx_to_app.send(message).is_err() {
+                                    return false;
+                                }
+                            }
+                        }
+                        Some(Ok(WsMessage::Close(_))) | Some(Err(_)) | None => return true,
+                        _ => {}
                     }
-                    _ => {}
                 }
+                _ = tokio::time::sleep_until(deadline) => return true,
             }
-        });
-        
-        Ok(Self {
-            tx: tx_to_ws,
-            rx: rx_from_ws,
-        })
+        }
     }
     
-    pub async fn send(&self, message: Message) -> Result<()> {
-        self.tx.send(message)
-            .map_err(|e| anyhow::anyhow!("Failed to send message: {}", e))
+    /// Drops what was waiting for the dead socket; the app is told sends fail while reconnecting.
+    fn discard_queued(&mut self) {
+        while self.rx_from_app.try_recv().is_ok() {
+            self.queued.fetch_sub(1, Ordering::Relaxed);
+        }
     }
     
-    #[allow(dead_code)]
-    pub async fn recv(&mut self) -> Option<Message> {
-        self.rx.recv().await
+    fn set_status(&self, status: ConnectionStatus) {
+        *self.status.lock().unwrap() = status;
     }
-}
\ No newline at end of file
+}
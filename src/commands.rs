@@ -0,0 +1,119 @@
+use crate::protocol::{ArgKind, CommandInfo};
+
+/// Commands handled entirely by the client: (name, usage, help, argument).
+const LOCAL_COMMANDS: &[(&str, &str, &str, ArgKind)] = &[
+    ("help", "/help", "List available commands", ArgKind::None),
+    ("clear", "/clear", "Clear the chat panel", ArgKind::None),
+    ("mentions", "/mentions", "List recent messages that mention you", ArgKind::None),
+    ("caption", "/caption [text]", "Set or clear the caption on your video", ArgKind::Text),
+    ("queue", "/queue [cancel [n]|edit [n]|clear]", "List, cancel or edit messages waiting to be sent", ArgKind::Text),
+    ("ignore", "/ignore [user]", "Hide a user's messages and video, or list who you ignore", ArgKind::User),
+    ("unignore", "/unignore <user>", "Show a user's messages and video again", ArgKind::User),
+    ("quit", "/quit", "Leave the chat", ArgKind::None),
+];
+
+pub enum LocalCommand {
+    Help,
+    Clear,
+    Mentions,
+    Caption(Option<String>),
+    Queue(QueueCommand),
+    /// `None` lists who is ignored
+    Ignore(Option<String>),
+    Unignore(Option<String>),
+    Quit,
+}
+
+/// `/queue` subcommands; `n` counts from 1 and defaults to the newest queued message.
+#[derive(Debug, PartialEq, Eq)]
+pub enum QueueCommand {
+    List,
+    Cancel(Option<usize>),
+    Edit(Option<usize>),
+    Clear,
+    Invalid,
+}
+
+pub enum ParsedCommand {
+    Local(LocalCommand),
+    Server { name: String, args: String },
+    Unknown(String),
+}
+
+pub fn local_commands() -> Vec<CommandInfo> {
+    CommandInfo::from_table(LOCAL_COMMANDS)
+}
+
+/// Parses input after the leading `/`. Other names found in `commands` (as
+/// advertised by the server) are sent to the server; anything else is unknown.
+pub fn parse(line: &str, commands: &[CommandInfo]) -> ParsedCommand {
+    let (name, args) = match line.split_once(char::is_whitespace) {
+        Some((name, args)) => (name, args.trim()),
+        None => (line, ""),
+    };
+    let name = name.to_lowercase();
+
+    match name.as_str() {
+        "help" => ParsedCommand::Local(LocalCommand::Help),
+        "clear" => ParsedCommand::Local(LocalCommand::Clear),
+        "mentions" => ParsedCommand::Local(LocalCommand::Mentions),
+        "caption" => ParsedCommand::Local(LocalCommand::Caption(
+            Some(args.to_string()).filter(|a| !a.is_empty()),
+        )),
+        "queue" => ParsedCommand::Local(LocalCommand::Queue(parse_queue(args))),
+        "ignore" => ParsedCommand::Local(LocalCommand::Ignore(
+            Some(args.to_string()).filter(|a| !a.is_empty()),
+        )),
+        "unignore" => ParsedCommand::Local(LocalCommand::Unignore(
+            Some(args.to_string()).filter(|a| !a.is_empty()),
+        )),
+        "quit" => ParsedCommand::Local(LocalCommand::Quit),
+        _ if commands.iter().any(|c| c.name == name) => ParsedCommand::Server {
+            name,
+            args: args.to_string(),
+        },
+        _ => ParsedCommand::Unknown(name),
+    }
+}
+
+fn parse_queue(args: &str) -> QueueCommand {
+    let mut words = args.split_whitespace();
+    let action = words.next();
+    let number = match words.next().map(str::parse::<usize>) {
+        None => None,
+        Some(Ok(n)) if n > 0 => Some(n),
+        Some(_) => return QueueCommand::Invalid,
+    };
+    if words.next().is_some() {
+        return QueueCommand::Invalid;
+    }
+
+    match (action, number) {
+        (None, _) => QueueCommand::List,
+        (Some("cancel"), n) => QueueCommand::Cancel(n),
+        (Some("edit"), n) => QueueCommand::Edit(n),
+        (Some("clear"), None) => QueueCommand::Clear,
+        _ => QueueCommand::Invalid,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_queue_subcommands() {
+        assert_eq!(parse_queue(""), QueueCommand::List);
+        assert_eq!(parse_queue("cancel"), QueueCommand::Cancel(None));
+        assert_eq!(parse_queue("cancel 2"), QueueCommand::Cancel(Some(2)));
+        assert_eq!(parse_queue("  edit   1 "), QueueCommand::Edit(Some(1)));
+        assert_eq!(parse_queue("clear"), QueueCommand::Clear);
+    }
+
+    #[test]
+    fn rejects_malformed_queue_commands() {
+        for args in ["cancel 0", "cancel -1", "edit x", "edit 1 2", "clear 1", "list", "1"] {
+            assert_eq!(parse_queue(args), QueueCommand::Invalid, "{:?}", args);
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use crate::protocol::{ArgKind, CommandInfo, UserInfo};
+
+/// An in-progress Tab cycle: the token being completed and its candidates.
+pub struct Completion {
+    /// Byte offset in the input where the completed token starts
+    pub start: usize,
+    pub candidates: Vec<String>,
+    pub index: usize,
+}
+
+impl Completion {
+    /// Builds candidates for the token ending at the cursor, or `None` when nothing matches.
+    pub fn new(before_cursor: &str, users: &[UserInfo], commands: &[CommandInfo]) -> Option<Self> {
+        let start = before_cursor
+            .rfind(char::is_whitespace)
+            .map_or(0, |i| i + before_cursor[i..].chars().next().map_or(1, char::len_utf8));
+        let token = &before_cursor[start..];
+
+        let mut candidates: Vec<String> = if start == 0 && token.starts_with('/') {
+            // Command names
+            commands
+                .iter()
+                .filter(|c| starts_with_ignore_case(&c.name, &token[1..]))
+                .map(|c| format!("/{} ", c.name))
+                .collect()
+        } else {
+            let command_arg = before_cursor
+                .strip_prefix('/')
+                .and_then(|rest| rest.split_whitespace().next())
+                .and_then(|name| commands.iter().find(|c| c.name == name))
+                .map(|c| c.arg);
+            let first_arg = before_cursor[..start].split_whitespace().count() == 1;
+
+            match command_arg {
+                Some(ArgKind::User) if first_arg => user_candidates(users, token, " "),
+                Some(_) => Vec::new(),
+                // Plain chat: "name: " at line start, "@name " for mentions, "name " elsewhere
+                None if token.starts_with('@') => user_candidates(users, &token[1..], " ")
+                    .into_iter()
+                    .map(|name| format!("@{}", name))
+                    .collect(),
+                None if start == 0 => user_candidates(users, token, ": "),
+                None => user_candidates(users, token, " "),
+            }
+        };
+
+        candidates.sort_by_key(|c| c.to_lowercase());
+        candidates.dedup();
+        if candidates.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            start,
+            candidates,
+            index: 0,
+        })
+    }
+
+    pub fn current(&self) -> &str {
+        &self.candidates[self.index]
+    }
+
+    pub fn cycle(&mut self, forward: bool) {
+        let len = self.candidates.len();
+        self.index = if forward {
+            (self.index + 1) % len
+        } else {
+            (self.index + len - 1) % len
+        };
+    }
+}
+
+fn user_candidates(users: &[UserInfo], prefix: &str, suffix: &str) -> Vec<String> {
+    users
+        .iter()
+        .filter(|u| starts_with_ignore_case(&u.username, prefix))
+        .map(|u| format!("{}{}", u.username, suffix))
+        .collect()
+}
+
+fn starts_with_ignore_case(s: &str, prefix: &str) -> bool {
+    s.to_lowercase().starts_with(&prefix.to_lowercase())
+}
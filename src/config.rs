@@ -0,0 +1,146 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use crate::keymap::KeySettings;
+use crate::notify::NotificationSettings;
+use crate::ui::LayoutMode;
+
+/// Client settings persisted in `<config dir>/terminal-chat/config.toml`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClientConfig {
+    pub layout: LayoutMode,
+    /// Width of the video column, in percent of the terminal
+    pub video_percent: u16,
+    /// Width of the chat column; the users panel gets the rest
+    pub chat_percent: u16,
+    /// Below this many columns or rows the panels collapse into tabs
+    pub compact_width: u16,
+    pub compact_height: u16,
+    /// Chat messages kept for scrollback
+    pub history_size: usize,
+    /// Built-in theme name, theme file in `themes/`, or path to a `.toml` file
+    pub theme: String,
+    /// Sent when joining so others can recognise you across sessions; created on first join
+    pub identity: Option<Uuid>,
+    pub effects: EffectSettings,
+    pub notifications: NotificationSettings,
+    pub keys: KeySettings,
+    /// Ask before quitting with the keyboard
+    pub confirm_quit: bool,
+    /// People whose messages and video are hidden, by identity
+    pub ignored: Vec<IgnoredUser>,
+    /// Why the file couldn't be used, if defaults stand in for it; it isn't saved over then
+    #[serde(skip)]
+    pub load_error: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct IgnoredUser {
+    pub identity: Uuid,
+    /// Name they had when ignored, for listing them while they're away
+    pub username: String,
+}
+
+/// Tuning for the tachyonfx screen effects.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EffectSettings {
+    /// Turns all effects off when false
+    pub enabled: bool,
+    /// Fade-in and dissolve when the app starts, in milliseconds (0 skips them)
+    pub fade_ms: u32,
+    pub coalesce_ms: u32,
+    /// Period of the slow color drift in milliseconds, and how far it shifts lightness (0 turns it off)
+    pub drift_ms: u32,
+    pub drift_amount: f64,
+}
+
+impl Default for EffectSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            fade_ms: 500,
+            coalesce_ms: 300,
+            drift_ms: 8000,
+            drift_amount: 0.02,
+        }
+    }
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            layout: LayoutMode::Gallery,
+            video_percent: 30,
+            chat_percent: 50,
+            compact_width: 100,
+            compact_height: 30,
+            history_size: 1000,
+            theme: "default".to_string(),
+            identity: None,
+            effects: EffectSettings::default(),
+            notifications: NotificationSettings::default(),
+            keys: KeySettings::default(),
+            confirm_quit: true,
+            ignored: Vec::new(),
+            load_error: None,
+        }
+    }
+}
+
+impl ClientConfig {
+    pub fn dir() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("terminal-chat"))
+    }
+
+    pub fn path() -> Option<PathBuf> {
+        Self::dir().map(|dir| dir.join("config.toml"))
+    }
+
+    /// Reads the config file, falling back to defaults when there is none yet or it
+    /// can't be read (see `load_error`).
+    pub fn load() -> Self {
+        match Self::path() {
+            Some(path) => Self::load_from(&path),
+            None => Self::default(),
+        }
+    }
+
+    /// As `load`, from `path`.
+    pub fn load_from(path: &Path) -> Self {
+        if !path.exists() {
+            return Self::default();
+        }
+
+        let loaded = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))
+            .and_then(|text| {
+                toml::from_str::<Self>(&text).with_context(|| format!("Invalid config in {}", path.display()))
+            });
+        let mut config = match loaded {
+            Ok(config) => config,
+            Err(e) => Self { load_error: Some(format!("{:#}", e)), ..Self::default() },
+        };
+        // Hand-edited panel sizes have to leave room for every column
+        config.video_percent = config.video_percent.clamp(10, 70);
+        config.chat_percent = config.chat_percent.clamp(20, 90 - config.video_percent);
+        config
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if self.load_error.is_some() {
+            bail!("the config file couldn't be read, so it isn't overwritten");
+        }
+        let path = Self::path().context("No config directory on this platform")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+
+        let text = toml::to_string_pretty(self).context("Failed to serialize config")?;
+        std::fs::write(&path, text).with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
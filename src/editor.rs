@@ -0,0 +1,264 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+// Sent messages remembered for Up/Down recall
+const HISTORY_LIMIT: usize = 100;
+
+/// Multi-line text input with a grapheme-aware cursor and message history.
+#[derive(Default)]
+pub struct LineEditor {
+    text: String,
+    /// Byte offset into `text`, always on a grapheme boundary
+    cursor: usize,
+    history: Vec<String>,
+    /// Position while browsing history, and the unsent text to return to
+    history_pos: Option<usize>,
+    draft: String,
+}
+
+impl LineEditor {
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    pub fn set_text(&mut self, text: &str) {
+        self.text = text.to_string();
+        self.cursor = self.text.len();
+    }
+
+    /// Takes the current text for sending and records it in history.
+    pub fn submit(&mut self) -> String {
+        let text = std::mem::take(&mut self.text);
+        self.cursor = 0;
+        self.history_pos = None;
+        if self.history.last() != Some(&text) {
+            self.history.push(text.clone());
+            if self.history.len() > HISTORY_LIMIT {
+                self.history.remove(0);
+            }
+        }
+        text
+    }
+
+    pub fn before_cursor(&self) -> &str {
+        &self.text[..self.cursor]
+    }
+
+    /// Replaces `start..cursor` with `replacement`, leaving the cursor after it.
+    pub fn replace_before_cursor(&mut self, start: usize, replacement: &str) {
+        self.text.replace_range(start..self.cursor, replacement);
+        self.cursor = start + replacement.len();
+    }
+
+    pub fn insert_char(&mut self, c: char) {
+        self.text.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+    }
+
+    pub fn backspace(&mut self) {
+        if let Some(start) = self.prev_boundary(self.cursor) {
+            self.text.replace_range(start..self.cursor, "");
+            self.cursor = start;
+        }
+    }
+
+    pub fn delete(&mut self) {
+        if let Some(end) = self.next_boundary(self.cursor) {
+            self.text.replace_range(self.cursor..end, "");
+        }
+    }
+
+    pub fn move_left(&mut self) {
+        if let Some(pos) = self.prev_boundary(self.cursor) {
+            self.cursor = pos;
+        }
+    }
+
+    pub fn move_right(&mut self) {
+        if let Some(pos) = self.next_boundary(self.cursor) {
+            self.cursor = pos;
+        }
+    }
+
+    pub fn move_home(&mut self) {
+        self.cursor = self.line_start();
+    }
+
+    pub fn move_end(&mut self) {
+        self.cursor = self.text[self.cursor..]
+            .find('\n')
+            .map_or(self.text.len(), |i| self.cursor + i);
+    }
+
+    pub fn move_word_left(&mut self) {
+        self.cursor = self.word_start_before(self.cursor);
+    }
+
+    pub fn move_word_right(&mut self) {
+        self.cursor = self
+            .text
+            .unicode_word_indices()
+            .map(|(i, word)| i + word.len())
+            .find(|&end| end > self.cursor)
+            .unwrap_or(self.text.len());
+    }
+
+    /// Ctrl-W: delete the word before the cursor.
+    pub fn delete_word_before(&mut self) {
+        let start = self.word_start_before(self.cursor);
+        self.text.replace_range(start..self.cursor, "");
+        self.cursor = start;
+    }
+
+    /// Ctrl-U: delete from the start of the line to the cursor.
+    pub fn delete_to_line_start(&mut self) {
+        let start = self.line_start();
+        self.text.replace_range(start..self.cursor, "");
+        self.cursor = start;
+    }
+
+    /// Ctrl-K: delete from the cursor to the end of the line.
+    pub fn delete_to_line_end(&mut self) {
+        let end = self.text[self.cursor..]
+            .find('\n')
+            .map_or(self.text.len(), |i| self.cursor + i);
+        self.text.replace_range(self.cursor..end, "");
+    }
+
+    /// Up: previous line of a multi-line message, or older history on the first line.
+    pub fn up(&mut self) {
+        if !self.move_up() {
+            self.history_prev();
+        }
+    }
+
+    /// Down: next line of a multi-line message, or newer history on the last line.
+    pub fn down(&mut self) {
+        if !self.move_down() {
+            self.history_next();
+        }
+    }
+
+    fn move_up(&mut self) -> bool {
+        let start = self.line_start();
+        if start == 0 {
+            return false;
+        }
+        let column = self.text[start..self.cursor].width();
+        let prev_start = self.text[..start - 1].rfind('\n').map_or(0, |i| i + 1);
+        self.cursor = self.offset_at_column(prev_start, start - 1, column);
+        true
+    }
+
+    fn move_down(&mut self) -> bool {
+        let Some(newline) = self.text[self.cursor..].find('\n').map(|i| self.cursor + i) else {
+            return false;
+        };
+        let column = self.text[self.line_start()..self.cursor].width();
+        let next_start = newline + 1;
+        let next_end = self.text[next_start..].find('\n').map_or(self.text.len(), |i| next_start + i);
+        self.cursor = self.offset_at_column(next_start, next_end, column);
+        true
+    }
+
+    fn history_prev(&mut self) {
+        let pos = match self.history_pos {
+            Some(0) => return,
+            Some(pos) => pos - 1,
+            None if self.history.is_empty() => return,
+            None => {
+                self.draft = self.text.clone();
+                self.history.len() - 1
+            }
+        };
+        self.history_pos = Some(pos);
+        self.set_text(&self.history[pos].clone());
+    }
+
+    fn history_next(&mut self) {
+        let Some(pos) = self.history_pos else {
+            return;
+        };
+        if pos + 1 < self.history.len() {
+            self.history_pos = Some(pos + 1);
+            self.set_text(&self.history[pos + 1].clone());
+        } else {
+            self.history_pos = None;
+            let draft = std::mem::take(&mut self.draft);
+            self.set_text(&draft);
+        }
+    }
+
+    /// The part of the text visible in a `width` x `max_lines` box, scrolled so
+    /// the cursor stays in view, plus the cursor position within that box and
+    /// the index of the first line shown.
+    pub fn view(&self, width: usize, max_lines: usize) -> (Vec<String>, (u16, u16), usize) {
+        let width = width.max(1);
+        let before = &self.text[..self.cursor];
+        let row = before.matches('\n').count();
+        let column = before[self.line_start()..].width();
+        
+        let first_row = row.saturating_sub(max_lines.max(1) - 1);
+        let offset = column.saturating_sub(width - 1);
+        let lines = self
+            .text
+            .split('\n')
+            .skip(first_row)
+            .take(max_lines.max(1))
+            .map(|line| slice_columns(line, offset, width))
+            .collect();
+        
+        (lines, ((column - offset) as u16, (row - first_row) as u16), first_row)
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.text.matches('\n').count() + 1
+    }
+
+    fn line_start(&self) -> usize {
+        self.text[..self.cursor].rfind('\n').map_or(0, |i| i + 1)
+    }
+
+    fn prev_boundary(&self, pos: usize) -> Option<usize> {
+        self.text[..pos].grapheme_indices(true).next_back().map(|(i, _)| i)
+    }
+
+    fn next_boundary(&self, pos: usize) -> Option<usize> {
+        self.text[pos..].graphemes(true).next().map(|g| pos + g.len())
+    }
+
+    fn word_start_before(&self, pos: usize) -> usize {
+        self.text
+            .unicode_word_indices()
+            .map(|(i, _)| i)
+            .take_while(|&i| i < pos)
+            .last()
+            .unwrap_or(0)
+    }
+
+    /// Byte offset in `start..end` closest to display column `column`.
+    fn offset_at_column(&self, start: usize, end: usize, column: usize) -> usize {
+        let mut width = 0;
+        for (i, g) in self.text[start..end].grapheme_indices(true) {
+            if width + g.width() > column {
+                return start + i;
+            }
+            width += g.width();
+        }
+        end
+    }
+}
+
+/// Graphemes of `line` that fall within display columns `skip..skip + width`.
+fn slice_columns(line: &str, skip: usize, width: usize) -> String {
+    let mut column = 0;
+    let mut out = String::new();
+    for g in line.graphemes(true) {
+        let w = g.width();
+        if column >= skip && column + w <= skip + width {
+            out.push_str(g);
+        }
+        column += w;
+    }
+    out
+}
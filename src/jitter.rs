@@ -0,0 +1,191 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::ascii::AsciiFrame;
+
+// Bounds on how long frames are held back to absorb jitter
+const MIN_DELAY: Duration = Duration::from_millis(20);
+const MAX_DELAY: Duration = Duration::from_millis(500);
+// Held frames per sender; beyond this the oldest are dropped
+const MAX_HELD: usize = 30;
+
+struct HeldFrame {
+    timestamp: u64,
+    username: String,
+    frame: AsciiFrame,
+}
+
+/// Playout buffer for one sender's video: holds frames back by a delay that
+/// follows the measured network jitter, then releases them at the sender's cadence.
+pub struct JitterBuffer {
+    /// Local clock that arrival times are measured against
+    epoch: Instant,
+    held: VecDeque<HeldFrame>,
+    /// Smallest recent `arrival - timestamp` in ms: maps the sender's clock onto ours
+    offset: Option<i64>,
+    last_transit: Option<i64>,
+    /// Smoothed variation in transit time in ms, as in RFC 3550
+    jitter: f64,
+    /// Timestamp of the frame last released; anything older arrives too late
+    played: Option<u64>,
+    pub dropped: u64,
+}
+
+impl JitterBuffer {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            epoch: now,
+            held: VecDeque::new(),
+            offset: None,
+            last_transit: None,
+            jitter: 0.0,
+            played: None,
+            dropped: 0,
+        }
+    }
+
+    /// Takes a frame stamped `timestamp` (ms on the sender's clock) that arrived at `now`.
+    pub fn push(&mut self, timestamp: u64, username: String, frame: AsciiFrame, now: Instant) {
+        if self.played.is_some_and(|played| timestamp <= played) {
+            self.dropped += 1;
+            return;
+        }
+
+        let transit = now.duration_since(self.epoch).as_millis() as i64 - timestamp as i64;
+        if let Some(last) = self.last_transit {
+            self.jitter += ((transit - last).abs() as f64 - self.jitter) / 16.0;
+        }
+        self.last_transit = Some(transit);
+        // Creep upwards so a path that got slower is followed, just not instantly
+        self.offset = Some(self.offset.map_or(transit, |offset| transit.min(offset + 1)));
+
+        let at = self.held.partition_point(|held| held.timestamp < timestamp);
+        if self.held.get(at).is_some_and(|held| held.timestamp == timestamp) {
+            self.dropped += 1;
+            return;
+        }
+        self.held.insert(at, HeldFrame { timestamp, username, frame });
+        while self.held.len() > MAX_HELD {
+            self.held.pop_front();
+            self.dropped += 1;
+        }
+    }
+
+    /// The newest frame whose playout time has come; older ones due with it are dropped as late.
+    pub fn pop_due(&mut self, now: Instant) -> Option<(String, AsciiFrame)> {
+        let due = self.held.iter().take_while(|held| self.due_at(held.timestamp) <= now).count();
+        if due == 0 {
+            return None;
+        }
+        self.dropped += due as u64 - 1;
+        let held = self.held.drain(..due).next_back()?;
+        self.played = Some(held.timestamp);
+        Some((held.username, held.frame))
+    }
+
+    /// When the next held frame is due, if any are waiting.
+    pub fn next_due(&self) -> Option<Instant> {
+        self.held.front().map(|held| self.due_at(held.timestamp))
+    }
+
+    /// Current hold-back: a few times the jitter, within bounds.
+    pub fn delay(&self) -> Duration {
+        Duration::from_micros((self.jitter * 3000.0) as u64).clamp(MIN_DELAY, MAX_DELAY)
+    }
+
+    fn due_at(&self, timestamp: u64) -> Instant {
+        let local = (timestamp as i64 + self.offset.unwrap_or(0)).max(0) as u64;
+        self.epoch + Duration::from_millis(local) + self.delay()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn push(buffer: &mut JitterBuffer, timestamp: u64, now: Instant) {
+        buffer.push(timestamp, timestamp.to_string(), AsciiFrame::new(1, 1), now);
+    }
+
+    fn popped(buffer: &mut JitterBuffer, now: Instant) -> Option<String> {
+        buffer.pop_due(now).map(|(name, _)| name)
+    }
+
+    #[test]
+    fn holds_frames_back_then_keeps_the_senders_pace() {
+        let t0 = Instant::now();
+        let mut buffer = JitterBuffer::new(t0);
+        push(&mut buffer, 0, t0);
+        push(&mut buffer, 100, t0 + ms(100));
+
+        assert_eq!(popped(&mut buffer, t0), None);
+        assert_eq!(popped(&mut buffer, t0 + MIN_DELAY).as_deref(), Some("0"));
+        assert_eq!(popped(&mut buffer, t0 + ms(100)), None);
+        assert_eq!(buffer.next_due(), Some(t0 + ms(100) + MIN_DELAY));
+        assert_eq!(popped(&mut buffer, t0 + ms(100) + MIN_DELAY).as_deref(), Some("100"));
+        assert_eq!(buffer.dropped, 0);
+    }
+
+    #[test]
+    fn shows_only_the_newest_of_several_due_frames() {
+        let t0 = Instant::now();
+        let mut buffer = JitterBuffer::new(t0);
+        for timestamp in [0, 40, 80] {
+            push(&mut buffer, timestamp, t0 + ms(timestamp));
+        }
+
+        assert_eq!(popped(&mut buffer, t0 + ms(200)).as_deref(), Some("80"));
+        assert_eq!(buffer.dropped, 2);
+        assert_eq!(buffer.next_due(), None);
+    }
+
+    #[test]
+    fn drops_late_and_duplicate_frames() {
+        let t0 = Instant::now();
+        let mut buffer = JitterBuffer::new(t0);
+        push(&mut buffer, 100, t0);
+        push(&mut buffer, 100, t0);
+        assert_eq!(buffer.dropped, 1);
+
+        assert_eq!(popped(&mut buffer, t0 + ms(50)).as_deref(), Some("100"));
+        push(&mut buffer, 60, t0 + ms(60));
+        assert_eq!(buffer.dropped, 2);
+        assert_eq!(buffer.next_due(), None);
+    }
+
+    #[test]
+    fn delay_follows_jitter_within_bounds() {
+        let t0 = Instant::now();
+        let mut buffer = JitterBuffer::new(t0);
+        assert_eq!(buffer.delay(), MIN_DELAY);
+
+        // Every other frame takes 60ms longer to arrive
+        for i in 0..50 {
+            let timestamp = i * 40;
+            push(&mut buffer, timestamp, t0 + ms(timestamp + (i % 2) * 60));
+        }
+        assert!(buffer.delay() > MIN_DELAY);
+        assert!(buffer.delay() <= MAX_DELAY);
+
+        // Wild swings still don't hold frames back past the cap
+        for i in 50..100 {
+            let timestamp = i * 40;
+            push(&mut buffer, timestamp, t0 + ms(timestamp + (i % 2) * 5000));
+        }
+        assert_eq!(buffer.delay(), MAX_DELAY);
+    }
+
+    #[test]
+    fn keeps_a_bounded_number_of_frames() {
+        let t0 = Instant::now();
+        let mut buffer = JitterBuffer::new(t0);
+        for timestamp in 0..MAX_HELD as u64 + 5 {
+            push(&mut buffer, timestamp, t0);
+        }
+        assert_eq!(buffer.dropped, 5);
+    }
+}
@@ -0,0 +1,442 @@
+use anyhow::{bail, Context, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Everything a key can be bound to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    Help,
+    Quit,
+    CycleLayout,
+    /// Small terminals only: Chat / Video / Users tabs
+    NextTab,
+    PrevPage,
+    NextPage,
+    Search,
+    ScrollUp,
+    ScrollDown,
+    ScrollTop,
+    ScrollBottom,
+    ShrinkVideo,
+    GrowVideo,
+    SelectPrev,
+    SelectNext,
+    /// Ignore or unignore the participant selected in the users panel
+    ToggleIgnore,
+    Complete,
+    CompleteBack,
+    Submit,
+    Newline,
+    CursorLeft,
+    CursorRight,
+    WordLeft,
+    WordRight,
+    LineStart,
+    LineEnd,
+    DeleteBack,
+    DeleteForward,
+    DeleteWord,
+    DeleteToStart,
+    DeleteToEnd,
+    HistoryPrev,
+    HistoryNext,
+    /// Vi: leave insert mode
+    NormalMode,
+    /// Vi: insert before the cursor, after it, at line end or at line start
+    Insert,
+    Append,
+    AppendEnd,
+    InsertStart,
+    /// Removes a binding from the preset
+    None,
+}
+
+impl Action {
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Help => "Show this help",
+            Action::Quit => "Quit",
+            Action::CycleLayout => "Cycle layout",
+            Action::NextTab => "Next tab (small terminals)",
+            Action::PrevPage => "Previous gallery page",
+            Action::NextPage => "Next gallery page",
+            Action::Search => "Search chat history",
+            Action::ScrollUp => "Scroll chat up a page",
+            Action::ScrollDown => "Scroll chat down a page",
+            Action::ScrollTop => "Jump to oldest message",
+            Action::ScrollBottom => "Jump to newest message",
+            Action::ShrinkVideo => "Narrow the video column",
+            Action::GrowVideo => "Widen the video column",
+            Action::SelectPrev => "Select previous participant",
+            Action::SelectNext => "Select next participant",
+            Action::ToggleIgnore => "Ignore / unignore selected participant",
+            Action::Complete => "Complete name or command",
+            Action::CompleteBack => "Previous completion",
+            Action::Submit => "Send message",
+            Action::Newline => "Insert a new line",
+            Action::CursorLeft => "Cursor left",
+            Action::CursorRight => "Cursor right",
+            Action::WordLeft => "Previous word",
+            Action::WordRight => "Next word",
+            Action::LineStart => "Start of line, or oldest message when empty",
+            Action::LineEnd => "End of line, or newest message when empty",
+            Action::DeleteBack => "Delete character before cursor",
+            Action::DeleteForward => "Delete character under cursor",
+            Action::DeleteWord => "Delete word before cursor",
+            Action::DeleteToStart => "Delete to start of line",
+            Action::DeleteToEnd => "Delete to end of line",
+            Action::HistoryPrev => "Line up / previous sent message",
+            Action::HistoryNext => "Line down / next sent message",
+            Action::NormalMode => "Normal mode",
+            Action::Insert => "Insert mode",
+            Action::Append => "Insert after cursor",
+            Action::AppendEnd => "Insert at end of line",
+            Action::InsertStart => "Insert at start of line",
+            Action::None => "Unbound",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Preset {
+    #[default]
+    Default,
+    Emacs,
+    /// Modal: Esc switches to a normal mode with vi motions
+    Vi,
+}
+
+/// `[keys]` in the config file.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeySettings {
+    pub preset: Preset,
+    /// Overrides on top of the preset, e.g. `"ctrl+q" = "quit"` or `"esc" = "none"`
+    pub bindings: BTreeMap<String, Action>,
+    /// Vi normal-mode overrides
+    pub normal: BTreeMap<String, Action>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct KeyBinding {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    /// Normalizes terminal differences: Shift is implied by the character itself.
+    pub fn from_event(key: KeyEvent) -> Self {
+        let mut modifiers = key.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        if matches!(key.code, KeyCode::Char(_) | KeyCode::BackTab) {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        Self { code: key.code, modifiers }
+    }
+
+    /// Parses strings like `ctrl+f`, `alt+enter`, `f2`, `pageup` or `?`.
+    pub fn parse(text: &str) -> Result<Self> {
+        let parts: Vec<&str> = if text == "+" { vec!["+"] } else { text.split('+').collect() };
+        let (key, modifier_names) = parts.split_last().context("Empty key")?;
+        let mut modifiers = KeyModifiers::NONE;
+        for name in modifier_names {
+            modifiers |= match name.to_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" | "meta" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                other => bail!("Unknown modifier {} in {}", other, text),
+            };
+        }
+
+        let lower = key.to_lowercase();
+        let code = match lower.as_str() {
+            "esc" | "escape" => KeyCode::Esc,
+            "enter" | "return" => KeyCode::Enter,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "backspace" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "insert" => KeyCode::Insert,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "space" => KeyCode::Char(' '),
+            _ if lower.len() > 1 && lower.starts_with('f') && lower[1..].parse::<u8>().is_ok() => {
+                KeyCode::F(lower[1..].parse().unwrap())
+            }
+            _ if key.chars().count() == 1 => KeyCode::Char(key.chars().next().unwrap()),
+            _ => bail!("Unknown key {}", text),
+        };
+        Ok(Self::from_event(KeyEvent::new(code, modifiers)))
+    }
+
+    /// Label for the help overlay, e.g. `Ctrl+F`.
+    pub fn label(&self) -> String {
+        let mut label = String::new();
+        for (modifier, name) in [
+            (KeyModifiers::CONTROL, "Ctrl+"),
+            (KeyModifiers::ALT, "Alt+"),
+            (KeyModifiers::SHIFT, "Shift+"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                label.push_str(name);
+            }
+        }
+        match self.code {
+            KeyCode::Char(' ') => label.push_str("Space"),
+            // Ctrl+F reads better than Ctrl+f; plain letters keep their case
+            KeyCode::Char(c) if !self.modifiers.is_empty() => label.extend(c.to_uppercase()),
+            KeyCode::Char(c) => label.push(c),
+            KeyCode::F(n) => label.push_str(&format!("F{}", n)),
+            KeyCode::BackTab => label.push_str("Shift+Tab"),
+            KeyCode::PageUp => label.push_str("PgUp"),
+            KeyCode::PageDown => label.push_str("PgDn"),
+            code => label.push_str(&format!("{:?}", code)),
+        }
+        label
+    }
+
+    /// Plain characters only act as shortcuts while there's no text to type into.
+    pub fn is_text(&self) -> bool {
+        matches!(self.code, KeyCode::Char(_)) && self.modifiers.is_empty()
+    }
+}
+
+/// Key bindings in effect: the preset plus the user's overrides.
+pub struct Keymap {
+    pub preset: Preset,
+    bindings: HashMap<KeyBinding, Action>,
+    /// Vi normal mode only
+    normal: HashMap<KeyBinding, Action>,
+}
+
+impl Keymap {
+    pub fn new(settings: &KeySettings) -> Result<Self> {
+        let mut bindings = table(DEFAULT_KEYS);
+        let mut normal = HashMap::new();
+        match settings.preset {
+            Preset::Default => {}
+            Preset::Emacs => bindings.extend(table(EMACS_KEYS)),
+            Preset::Vi => {
+                bindings.extend(table(VI_INSERT_KEYS));
+                normal = table(VI_NORMAL_KEYS);
+            }
+        }
+
+        for (overrides, target) in [(&settings.bindings, &mut bindings), (&settings.normal, &mut normal)] {
+            for (key, action) in overrides {
+                let binding = KeyBinding::parse(key).with_context(|| format!("Invalid key binding \"{}\"", key))?;
+                target.insert(binding, *action);
+            }
+        }
+
+        Ok(Self {
+            preset: settings.preset,
+            bindings,
+            normal,
+        })
+    }
+
+    pub fn lookup(&self, key: KeyEvent, normal_mode: bool, input_empty: bool) -> Option<Action> {
+        let binding = KeyBinding::from_event(key);
+        let table = if normal_mode { &self.normal } else { &self.bindings };
+        if binding.is_text() && !input_empty && !normal_mode {
+            return None;
+        }
+        table.get(&binding).copied().filter(|a| *a != Action::None)
+    }
+
+    /// Label of a key bound to `action` for on-screen hints, preferring the
+    /// shortest; vi normal mode keys are only used when nothing else is bound.
+    pub fn label_for(&self, action: Action) -> Option<String> {
+        let shortest = |table: &HashMap<KeyBinding, Action>| {
+            table
+                .iter()
+                .filter(|(_, a)| **a == action)
+                .map(|(binding, _)| binding.label())
+                .min_by_key(|label| (label.len(), label.clone()))
+        };
+        shortest(&self.bindings).or_else(|| shortest(&self.normal))
+    }
+
+    /// Bindings grouped by action, in a stable order, for the help overlay.
+    pub fn describe(&self, normal_mode: bool) -> Vec<(String, &'static str)> {
+        let table = if normal_mode { &self.normal } else { &self.bindings };
+        let mut by_action: Vec<(Action, Vec<String>)> = Vec::new();
+        for (binding, action) in table {
+            if *action == Action::None {
+                continue;
+            }
+            match by_action.iter_mut().find(|(a, _)| a == action) {
+                Some((_, keys)) => keys.push(binding.label()),
+                None => by_action.push((*action, vec![binding.label()])),
+            }
+        }
+        by_action.sort_by_key(|(action, _)| *action as usize);
+        by_action
+            .into_iter()
+            .map(|(action, mut keys)| {
+                keys.sort();
+                (keys.join(", "), action.description())
+            })
+            .collect()
+    }
+}
+
+fn table(keys: &[(&str, Action)]) -> HashMap<KeyBinding, Action> {
+    keys.iter()
+        .map(|(key, action)| (KeyBinding::parse(key).expect("valid built-in binding"), *action))
+        .collect()
+}
+
+const DEFAULT_KEYS: &[(&str, Action)] = &[
+    ("f1", Action::Help),
+    ("esc", Action::Quit),
+    ("f2", Action::CycleLayout),
+    ("f3", Action::NextTab),
+    ("f5", Action::PrevPage),
+    ("f6", Action::NextPage),
+    ("ctrl+f", Action::Search),
+    ("pageup", Action::ScrollUp),
+    ("pagedown", Action::ScrollDown),
+    ("ctrl+home", Action::ScrollTop),
+    ("ctrl+end", Action::ScrollBottom),
+    ("alt+left", Action::ShrinkVideo),
+    ("alt+right", Action::GrowVideo),
+    ("alt+up", Action::SelectPrev),
+    ("alt+down", Action::SelectNext),
+    ("f8", Action::ToggleIgnore),
+    ("tab", Action::Complete),
+    ("backtab", Action::CompleteBack),
+    ("enter", Action::Submit),
+    // Shift+Enter needs terminal support; Alt+Enter works everywhere
+    ("alt+enter", Action::Newline),
+    ("shift+enter", Action::Newline),
+    ("left", Action::CursorLeft),
+    ("right", Action::CursorRight),
+    ("ctrl+left", Action::WordLeft),
+    ("ctrl+right", Action::WordRight),
+    ("home", Action::LineStart),
+    ("end", Action::LineEnd),
+    ("backspace", Action::DeleteBack),
+    ("delete", Action::DeleteForward),
+    ("ctrl+w", Action::DeleteWord),
+    ("ctrl+u", Action::DeleteToStart),
+    ("up", Action::HistoryPrev),
+    ("down", Action::HistoryNext),
+];
+
+// Readline-style editing; search moves to Ctrl+R since Ctrl+F is cursor right
+const EMACS_KEYS: &[(&str, Action)] = &[
+    ("ctrl+a", Action::LineStart),
+    ("ctrl+e", Action::LineEnd),
+    ("ctrl+b", Action::CursorLeft),
+    ("ctrl+f", Action::CursorRight),
+    ("alt+b", Action::WordLeft),
+    ("alt+f", Action::WordRight),
+    ("ctrl+d", Action::DeleteForward),
+    ("ctrl+h", Action::DeleteBack),
+    ("alt+backspace", Action::DeleteWord),
+    ("ctrl+k", Action::DeleteToEnd),
+    ("ctrl+p", Action::HistoryPrev),
+    ("ctrl+n", Action::HistoryNext),
+    ("ctrl+r", Action::Search),
+    ("ctrl+v", Action::ScrollDown),
+    ("alt+v", Action::ScrollUp),
+    ("alt+<", Action::ScrollTop),
+    ("alt+>", Action::ScrollBottom),
+    ("ctrl+x", Action::Quit),
+];
+
+// Insert mode types as usual; Esc goes to normal mode instead of quitting
+const VI_INSERT_KEYS: &[(&str, Action)] = &[
+    ("esc", Action::NormalMode),
+];
+
+const VI_NORMAL_KEYS: &[(&str, Action)] = &[
+    ("i", Action::Insert),
+    ("a", Action::Append),
+    ("A", Action::AppendEnd),
+    ("I", Action::InsertStart),
+    ("h", Action::CursorLeft),
+    ("l", Action::CursorRight),
+    ("left", Action::CursorLeft),
+    ("right", Action::CursorRight),
+    ("b", Action::WordLeft),
+    ("w", Action::WordRight),
+    ("0", Action::LineStart),
+    ("$", Action::LineEnd),
+    ("x", Action::DeleteForward),
+    ("X", Action::DeleteBack),
+    ("D", Action::DeleteToEnd),
+    ("k", Action::HistoryPrev),
+    ("j", Action::HistoryNext),
+    ("up", Action::HistoryPrev),
+    ("down", Action::HistoryNext),
+    ("ctrl+u", Action::ScrollUp),
+    ("ctrl+d", Action::ScrollDown),
+    ("pageup", Action::ScrollUp),
+    ("pagedown", Action::ScrollDown),
+    ("g", Action::ScrollTop),
+    ("G", Action::ScrollBottom),
+    ("/", Action::Search),
+    ("?", Action::Help),
+    ("f1", Action::Help),
+    ("q", Action::Quit),
+    ("enter", Action::Submit),
+    ("tab", Action::Complete),
+    ("backtab", Action::CompleteBack),
+    ("f2", Action::CycleLayout),
+    ("f3", Action::NextTab),
+    ("f5", Action::PrevPage),
+    ("f6", Action::NextPage),
+    ("alt+left", Action::ShrinkVideo),
+    ("alt+right", Action::GrowVideo),
+    ("alt+up", Action::SelectPrev),
+    ("alt+down", Action::SelectNext),
+    ("f8", Action::ToggleIgnore),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binding(code: KeyCode, modifiers: KeyModifiers) -> KeyBinding {
+        KeyBinding { code, modifiers }
+    }
+
+    #[test]
+    fn parses_key_names_and_modifiers() {
+        assert_eq!(KeyBinding::parse("ctrl+f").unwrap(), binding(KeyCode::Char('f'), KeyModifiers::CONTROL));
+        assert_eq!(KeyBinding::parse("Alt+Enter").unwrap(), binding(KeyCode::Enter, KeyModifiers::ALT));
+        assert_eq!(
+            KeyBinding::parse("control+shift+pageup").unwrap(),
+            binding(KeyCode::PageUp, KeyModifiers::CONTROL | KeyModifiers::SHIFT)
+        );
+        assert_eq!(KeyBinding::parse("f12").unwrap(), binding(KeyCode::F(12), KeyModifiers::NONE));
+        assert_eq!(KeyBinding::parse("space").unwrap(), binding(KeyCode::Char(' '), KeyModifiers::NONE));
+        assert_eq!(KeyBinding::parse("?").unwrap(), binding(KeyCode::Char('?'), KeyModifiers::NONE));
+        assert_eq!(KeyBinding::parse("+").unwrap(), binding(KeyCode::Char('+'), KeyModifiers::NONE));
+        assert_eq!(KeyBinding::parse("f").unwrap(), binding(KeyCode::Char('f'), KeyModifiers::NONE));
+    }
+
+    #[test]
+    fn shift_is_implied_by_characters() {
+        assert_eq!(KeyBinding::parse("shift+a").unwrap(), binding(KeyCode::Char('a'), KeyModifiers::NONE));
+        assert_eq!(KeyBinding::parse("shift+backtab").unwrap(), binding(KeyCode::BackTab, KeyModifiers::NONE));
+    }
+
+    #[test]
+    fn rejects_unknown_keys_and_modifiers() {
+        for text in ["", "super+f", "ctrl+", "fx", "ctrl+nokey"] {
+            assert!(KeyBinding::parse(text).is_err(), "{:?}", text);
+        }
+    }
+}
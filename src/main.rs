@@ -1,26 +1,61 @@
+mod adaptive;
 mod ascii;
 mod client;
+mod commands;
+mod completion;
+mod config;
+mod editor;
+mod jitter;
+mod keymap;
+mod markdown;
+mod motion;
+mod notify;
+mod overlay;
 mod protocol;
 mod server;
+mod theme;
 mod ui;
 mod webcam;
 
 use anyhow::Result;
+use base64::Engine;
 use clap::Parser;
-use crossterm::event::{self, Event};
+use futures_util::StreamExt;
+use crossterm::event::{DisableMouseCapture, EnableMouseCapture, Event, EventStream};
+use crossterm::{execute, style::Print};
 use ratatui::prelude::*;
 use std::io::IsTerminal;
+use std::collections::HashMap;
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::sync::mpsc;
 use tracing_subscriber;
 use uuid::Uuid;
 
-use crate::client::ChatClient;
-use crate::protocol::Message;
+use crate::adaptive::QualityController;
+use crate::client::{ChatClient, ConnectionStatus};
+use crate::config::ClientConfig;
+use crate::keymap::Keymap;
+use crate::motion::{FrameDecision, MotionGate};
+use crate::overlay::{OverlayContent, OverlayPosition, OverlayStage};
+use crate::protocol::{Message, VideoLayer, SMALL_LAYER_DIVISOR};
 use crate::server::{ServerState, start_server};
-use crate::ui::{App, UserAction};
+use crate::theme::Theme;
+use crate::ui::{App, MessageKind, Outgoing, UserAction};
 use crate::webcam::WebcamCapture;
 
+// Drop outgoing video instead of queueing more once the socket is this far behind
+const MAX_QUEUED_FRAMES: usize = 16;
+
+// Redraws are coalesced to at most one per this interval
+const MIN_FRAME_INTERVAL: Duration = Duration::from_millis(16);
+// Redraw at least this often, for countdowns and timeouts, even when nothing arrives
+const HOUSEKEEPING_INTERVAL: Duration = Duration::from_secs(1);
+
+// Capture size used until the video panel has been laid out
+const DEFAULT_VIDEO_SIZE: (u16, u16) = (40, 30);
+// Upper bound for capture, whether sized automatically or on the command line
+const MAX_VIDEO_SIZE: (u16, u16) = (160, 90);
+
 #[derive(Parser, Debug, Clone)]
 #[command(name = "Terminal Chat", about = "ASCII video chat in your terminal")]
 struct Args {
@@ -40,17 +75,53 @@ struct Args {
     #[arg(long)]
     ngrok: bool,
     
-    /// Video width in characters
-    #[arg(long, default_value_t = 40)]
-    video_width: u32,
+    /// Video width in characters (default: fit to the video panel)
+    #[arg(long)]
+    video_width: Option<u16>,
     
-    /// Video height in characters
-    #[arg(long, default_value_t = 30)]
-    video_height: u32,
+    /// Video height in characters (default: fit to the video panel)
+    #[arg(long)]
+    video_height: Option<u16>,
     
     /// Video FPS
     #[arg(long, default_value_t = 15)]
     fps: u32,
+    
+    /// Stamp your username onto outgoing video at this position
+    #[arg(long, value_enum)]
+    name_tag: Option<OverlayPosition>,
+    
+    /// Stamp a clock onto outgoing video at this position
+    #[arg(long, value_enum)]
+    clock: Option<OverlayPosition>,
+    
+    /// Caption line stamped onto outgoing video
+    #[arg(long)]
+    caption: Option<String>,
+    
+    /// Caption position
+    #[arg(long, value_enum, default_value_t = OverlayPosition::Bottom)]
+    caption_position: OverlayPosition,
+    
+    /// Overlay text color (hex RRGGBB)
+    #[arg(long, default_value = "ffffff", value_parser = overlay::parse_hex_color)]
+    overlay_color: (u8, u8, u8),
+    
+    /// Fraction of changed cells needed to send a frame (0 sends every frame)
+    #[arg(long, default_value_t = 0.02)]
+    motion_threshold: f32,
+    
+    /// Seconds between keepalive frames while the picture is static
+    #[arg(long, default_value_t = 2)]
+    keepalive_secs: u64,
+    
+    /// Leave the mouse to the terminal (keeps native text selection working)
+    #[arg(long)]
+    no_mouse: bool,
+    
+    /// Color theme: default, light, high-contrast, monochrome, solarized, or a theme file (overrides the config)
+    #[arg(long)]
+    theme: Option<String>,
 }
 
 #[tokio::main]
@@ -93,11 +164,19 @@ async fn run_client(args: Args, url: String) -> Result<()> {
         return Err(anyhow::anyhow!("Not running in a TTY"));
     }
     
+    let config = ClientConfig::load();
+    let theme = Theme::load(args.theme.as_deref().unwrap_or(&config.theme))?;
+    let keymap = Keymap::new(&config.keys)?;
+    
     let mut terminal = ratatui::init();
     terminal.clear()?;
+    if !args.no_mouse {
+        execute!(std::io::stdout(), EnableMouseCapture)?;
+    }
     
-    let result = run_chat_client(&mut terminal, args, url).await;
+    let result = run_chat_client(&mut terminal, args, url, config, theme, keymap, None).await;
     
+    let _ = execute!(std::io::stdout(), DisableMouseCapture);
     ratatui::restore();
     result
 }
@@ -110,6 +189,10 @@ async fn run_host(args: Args) -> Result<()> {
         return Err(anyhow::anyhow!("Not running in a TTY"));
     }
     
+    let config = ClientConfig::load();
+    let theme = Theme::load(args.theme.as_deref().unwrap_or(&config.theme))?;
+    let keymap = Keymap::new(&config.keys)?;
+    
     // Start server in background
     let state = ServerState::new();
     let server_state = state.clone();
@@ -137,12 +220,13 @@ async fn run_host(args: Args) -> Result<()> {
     
     let mut terminal = ratatui::init();
     terminal.clear()?;
+    if !args.no_mouse {
+        execute!(std::io::stdout(), EnableMouseCapture)?;
+    }
     
-    let mut app = App::new();
-    app.ngrok_url = ngrok_url;
-    
-    let result = run_chat_client(&mut terminal, args, url).await;
+    let result = run_chat_client(&mut terminal, args, url, config, theme, keymap, ngrok_url).await;
     
+    let _ = execute!(std::io::stdout(), DisableMouseCapture);
     ratatui::restore();
     result
 }
@@ -151,128 +235,390 @@ async fn run_chat_client(
     terminal: &mut Terminal<impl Backend>,
     args: Args,
     url: String,
+    config: ClientConfig,
+    theme: Theme,
+    keymap: Keymap,
+    ngrok_url: Option<String>,
 ) -> Result<()> {
-    let mut app = App::new();
+    let mut app = App::new(config, theme, keymap);
+    app.ngrok_url = ngrok_url;
     let mut last_draw = Instant::now();
     
     // Initialize webcam (optional - continue even if it fails)
-    let webcam = WebcamCapture::new(args.video_width, args.video_height, args.fps)?;
+    let requested = requested_video_size(&args);
+    let mut video_size = (
+        requested.0.unwrap_or(DEFAULT_VIDEO_SIZE.0),
+        requested.1.unwrap_or(DEFAULT_VIDEO_SIZE.1),
+    );
+    let mut webcam = WebcamCapture::new(video_size.0 as u32, video_size.1 as u32, args.fps)?;
     
     let (_tx_ui, _rx_ui) = mpsc::unbounded_channel::<()>();
     let mut client: Option<ChatClient> = None;
     let user_id = Uuid::new_v4();
+    let mut subscribed_layers: HashMap<Uuid, VideoLayer> = HashMap::new();
+    // Set when a notification retitled the window, cleared on the next key press
+    let mut title_changed = false;
+    
+    let mut overlays = OverlayStage::default();
+    if let Some(position) = args.name_tag {
+        overlays.add(OverlayContent::NameTag, position, args.overlay_color);
+    }
+    if let Some(position) = args.clock {
+        overlays.add(OverlayContent::Clock, position, args.overlay_color);
+    }
+    overlays.set_caption(args.caption.clone(), args.caption_position, args.overlay_color);
+    
+    let mut motion = MotionGate::new(args.motion_threshold, Duration::from_secs(args.keepalive_secs));
+    let mut quality = QualityController::new(video_size.0, video_size.1, args.fps);
+    let mut preferred_size = (0, 0);
+    app.quality = Some(quality.current());
     
-    // Frame sending task
-    let mut frame_interval = tokio::time::interval(Duration::from_millis(1000 / args.fps as u64));
-    frame_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let mut events = EventStream::new();
+    // Time-based state (countdowns, delivery timeouts, stale video) only needs an occasional redraw
+    let mut housekeeping = tokio::time::interval(HOUSEKEEPING_INTERVAL);
+    housekeeping.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let mut render_tick = tokio::time::interval(MIN_FRAME_INTERVAL);
+    render_tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let mut dirty = true;
     
     loop {
-        // Handle UI events
-        if event::poll(Duration::from_millis(10))? {
-            if let Event::Key(key) = event::read()? {
-                if let Some(action) = app.handle_key(key.code)? {
+        let playout_at = app.next_playout();
+        tokio::select! {
+            event = events.next() => {
+                let Some(event) = event else {
+                    break;
+                };
+                let action = match event? {
+                    Event::Key(key) => {
+                        if std::mem::take(&mut title_changed) {
+                            notify::reset_title(&mut std::io::stdout())?;
+                        }
+                        dirty = true;
+                        app.handle_key(key)?
+                    }
+                    Event::Mouse(mouse) => {
+                        dirty = true;
+                        app.handle_mouse(mouse)
+                    }
+                    Event::Resize(..) => {
+                        dirty = true;
+                        None
+                    }
+                    _ => None,
+                };
+                if let Some(action) = action {
                     match action {
                         UserAction::JoinChat(username) => {
+                            overlays.username = Some(username.clone());
+                            let identity = app.identity();
                             // Connect to server
                             match ChatClient::connect(&url).await {
                                 Ok(c) => {
                                     c.send(Message::Join {
                                         id: user_id,
                                         username: username.clone(),
+                                        identity: Some(identity),
                                     }).await?;
                                     client = Some(c);
                                 }
                                 Err(e) => {
                                     app.add_message(
                                         "System".to_string(),
-                                        format!("Failed to connect: {}", e)
+                                        format!("Failed to connect: {}; retrying in the background", e)
                                     );
+                                    client = Some(ChatClient::connect_later(&url, username, identity));
                                 }
                             }
                         }
                         UserAction::SendMessage(text) => {
+                            // Sent below, in order, whenever the connection is up
+                            app.outbox.push_back(Outgoing::new(text));
+                        }
+                        UserAction::Command { name, args } => {
                             if let Some(ref c) = client {
-                                c.send(Message::Chat {
-                                    id: user_id,
-                                    username: String::new(), // Server will fill this
-                                    text,
-                                    timestamp: SystemTime::now()
-                                        .duration_since(UNIX_EPOCH)
-                                        .unwrap()
-                                        .as_secs(),
-                                }).await?;
+                                if let Err(e) = c.send(Message::Command { name, args }).await {
+                                    app.add_message("Error".to_string(), format!("Command not sent: {}", e));
+                                }
                             }
                         }
+                        UserAction::SetCaption(caption) => {
+                            overlays.set_caption(caption, args.caption_position, args.overlay_color);
+                        }
+                        UserAction::CopyToClipboard(text) => {
+                            // OSC 52 asks the terminal itself to set the clipboard
+                            let encoded = base64::engine::general_purpose::STANDARD.encode(&text);
+                            execute!(std::io::stdout(), Print(format!("\x1b]52;c;{}\x07", encoded)))?;
+                            app.add_message("System".to_string(), format!("Copied {}", text));
+                        }
                     }
                 }
             }
-        }
-        
-        // Update video frame
-        if let Some(frame) = webcam.get_frame() {
-            app.update_video_frame(frame.clone());
             
-            // Send frame to server if connected periodically
-            if client.is_some() {
+            Some(mut frame) = webcam.next_frame() => {
+                // Camera frames only cost a redraw while the preview is visible
+                dirty |= app.shows_video();
+                
+                // Measure motion on the raw picture, before overlays like the clock change it
+                let decision = motion.check(&frame);
+                app.motion = motion.is_moving();
+                
+                overlays.apply(&mut frame);
+                app.update_video_frame(frame.clone());
+                
                 if let Some(ref c) = client {
-                    let _ = c.send(Message::VideoFrame {
-                        id: user_id,
-                        username: String::new(),
-                        frame: frame.serialize(),
-                    }).await;
-                }
-            }
-        }
-        
-        // Receive messages from server
-        if let Some(ref mut c) = client {
-            while let Ok(msg) = c.rx.try_recv() {
-                match msg {
-                    Message::Join { username, .. } => {
-                        app.add_message("System".to_string(), format!("{} joined", username));
+                    if let Some(level) = quality.observe(c.queue_depth(), c.send_latency()) {
+                        webcam.reconfigure(level.width, level.height, level.fps);
+                        app.quality = Some(level);
                     }
-                    Message::Leave { .. } => {
-                        app.add_message("System".to_string(), "A user left".to_string());
-                    }
-                    Message::Chat { username, text, .. } => {
-                        app.add_message(username, text);
-                    }
-                    Message::VideoFrame { username, frame, .. } => {
-                        if let Ok(ascii_frame) = crate::ascii::AsciiFrame::deserialize(&frame) {
-                            app.update_remote_frame(username, ascii_frame);
-                        }
-                    }
-                    Message::UserList { users } => {
-                        app.update_users(users);
-                    }
-                    Message::ServerInfo { ngrok_url, .. } => {
-                        if ngrok_url.is_some() {
-                            app.ngrok_url = ngrok_url;
+                }
+                
+                // Static scenes only go out as occasional keepalive frames
+                if !matches!(decision, FrameDecision::Skip) {
+                    if let Some(c) = client.as_ref().filter(|c| c.queue_depth() < MAX_QUEUED_FRAMES) {
+                        let timestamp_ms = SystemTime::now()
+                            .duration_since(UNIX_EPOCH)
+                            .unwrap()
+                            .as_millis() as u64;
+                        let small = frame.resize(
+                            frame.width / SMALL_LAYER_DIVISOR,
+                            frame.height / SMALL_LAYER_DIVISOR,
+                        );
+                        for (layer, layer_frame) in [(VideoLayer::Large, &frame), (VideoLayer::Small, &small)] {
+                            let _ = c.send(Message::VideoFrame {
+                                id: user_id,
+                                username: String::new(),
+                                frame: layer_frame.serialize(),
+                                layer,
+                                timestamp_ms,
+                                keepalive_ms: args.keepalive_secs * 1000,
+                            }).await;
                         }
                     }
-                    Message::Error { message } => {
-                        app.add_message("Error".to_string(), message);
+                }
+            }
+            
+            Some(message) = next_message(&mut client) => {
+                dirty = true;
+                if let Some(c) = client.as_mut() {
+                    let mut next = Some(message);
+                    while let Some(message) = next {
+                        handle_server_message(&mut app, c, message, &mut subscribed_layers, &mut preferred_size).await;
+                        // Take whatever else has already arrived before drawing
+                        next = c.rx.try_recv().ok();
                     }
                 }
+                // Keep the name tag on our video in step with /nick
+                if let Some(name) = app.own_name().filter(|&n| overlays.username.as_deref() != Some(n)) {
+                    overlays.username = Some(name.to_string());
+                }
+            }
+            
+            // A held remote frame is due; it's shown below
+            _ = tokio::time::sleep_until(playout_at.unwrap_or_else(Instant::now).into()), if playout_at.is_some() => {}
+            
+            _ = housekeeping.tick() => {
+                dirty = true;
+                app.expire_pending();
+            }
+            
+            // Catches up on a redraw that was held back, and drives the screen effects
+            _ = render_tick.tick(), if dirty || app.animating() => {
+                dirty = true;
+            }
+        }
+        
+        dirty |= app.play_remote_frames();
+        
+        // The client reconnects by itself; mirror its state in the status line
+        if let Some(ref c) = client {
+            let status = c.status();
+            if matches!(status, ConnectionStatus::Reconnecting { .. }) && app.connection == ConnectionStatus::Connected {
+                app.add_message("System".to_string(), "Connection lost, reconnecting…".to_string());
+            }
+            dirty |= status != app.connection;
+            app.connection = status;
+        }
+        
+        // Chat typed while offline (and anything just typed) goes out oldest first
+        if let Some(c) = client.as_ref().filter(|c| c.status() == ConnectionStatus::Connected) {
+            while let Some(outgoing) = app.outbox.front() {
+                let message_id = outgoing.message_id;
+                let sent = c.send(Message::Chat {
+                    id: user_id,
+                    username: String::new(), // Server will fill this
+                    text: outgoing.text.clone(),
+                    timestamp: SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs(),
+                    message_id: Some(message_id),
+                }).await;
+                if sent.is_err() {
+                    break;
+                }
+                if let Some(outgoing) = app.outbox.pop_front() {
+                    app.message_sent(message_id, outgoing.text);
+                }
             }
         }
         
-        // Draw UI
+        // Bell, desktop notification or window title for mentions, DMs and joins
+        for notification in std::mem::take(&mut app.notifications) {
+            let methods = app.config.notifications.methods(notification.event);
+            title_changed |= notify::emit(&mut std::io::stdout(), methods, &notification)?;
+        }
+        
+        if app.should_quit {
+            break;
+        }
+        
+        // Draw UI, at most once per frame interval however busy the inputs are
+        if !dirty || last_draw.elapsed() < MIN_FRAME_INTERVAL {
+            continue;
+        }
+        dirty = false;
         let elapsed = last_draw.elapsed();
         last_draw = Instant::now();
         
         terminal.draw(|f| ui::draw(f, &mut app, elapsed))?;
         
-        if app.should_quit {
-            break;
+        // Follow panel resizes: tell the server what we can show, capture what others can
+        if let Some(ref c) = client {
+            let tile = app.largest_tile_size();
+            if tile != preferred_size && tile.0 > 0 && tile.1 > 0 {
+                preferred_size = tile;
+                let _ = c.send(Message::PreferredSize { width: tile.0, height: tile.1 }).await;
+            }
+            
+            let target = capture_size(&args, &app);
+            if target != video_size && target.0 > 0 && target.1 > 0 {
+                video_size = target;
+                let level = quality.set_base(target.0, target.1, args.fps);
+                webcam.reconfigure(level.width, level.height, level.fps);
+                app.quality = Some(level);
+            }
         }
-        
-        tokio::time::sleep(Duration::from_millis(16)).await;
     }
     
+    // A clean close lets the server drop us at once instead of holding our place
+    if let Some(c) = client {
+        c.close().await;
+    }
+    if title_changed {
+        notify::reset_title(&mut std::io::stdout())?;
+    }
     Ok(())
 }
 
+/// Applies one message from the server to the UI.
+async fn handle_server_message(
+    app: &mut App,
+    c: &ChatClient,
+    message: Message,
+    subscribed_layers: &mut HashMap<Uuid, VideoLayer>,
+    preferred_size: &mut (u16, u16),
+) {
+    match message {
+        Message::Session { id, resumed, .. } => {
+            // The server forgets layer choices and sizes with the old connection
+            if app.own_id.is_some() {
+                subscribed_layers.clear();
+                *preferred_size = (0, 0);
+            }
+            app.session_started(id, resumed);
+        }
+        Message::Join { id, username, identity } => {
+            app.user_joined(id, username, identity);
+        }
+        Message::Leave { id } => {
+            subscribed_layers.remove(&id);
+            app.user_left(id);
+        }
+        Message::Chat { id, username, text, message_id, .. } => {
+            app.mark_active(id);
+            // Our own message, already on screen from when we sent it
+            if !message_id.is_some_and(|m| app.message_echoed(m)) {
+                app.receive_message(MessageKind::Chat, id, username, text);
+            }
+        }
+        Message::VideoFrame { id, username, frame, layer, timestamp_ms, keepalive_ms } => {
+            if let Ok(ascii_frame) = crate::ascii::AsciiFrame::deserialize(&frame) {
+                // Ask for the other layer once, if this one doesn't fit its tile
+                let wanted = layer.fit(ascii_frame.width, ascii_frame.height, app.remote_tile_size(id));
+                if wanted != layer && subscribed_layers.get(&id) != Some(&wanted) {
+                    subscribed_layers.insert(id, wanted);
+                    let _ = c.send(Message::SubscribeLayer { id, layer: wanted }).await;
+                }
+                app.queue_remote_frame(id, username, timestamp_ms, keepalive_ms, ascii_frame);
+            }
+        }
+        Message::UserList { users } => {
+            app.update_users(users);
+        }
+        Message::ServerInfo { ngrok_url, topic, .. } => {
+            if ngrok_url.is_some() {
+                app.ngrok_url = ngrok_url;
+            }
+            app.topic = topic;
+        }
+        Message::Error { message } => {
+            app.add_message("Error".to_string(), message);
+        }
+        Message::CommandList { commands } => {
+            app.set_server_commands(commands);
+        }
+        Message::Notice { text } => {
+            app.add_message("System".to_string(), text);
+        }
+        Message::Action { id, username, text, .. } => {
+            app.mark_active(id);
+            app.receive_message(MessageKind::Action, id, username, text);
+        }
+        Message::Direct { from, from_name, to_name, text, .. } => {
+            app.receive_direct(from, from_name, to_name, text);
+        }
+        Message::Topic { topic, set_by } => {
+            app.add_message("System".to_string(), format!("{} set the topic: {}", set_by, topic));
+            app.topic = Some(topic);
+        }
+        // Client-to-server only
+        Message::SubscribeLayer { .. }
+        | Message::PreferredSize { .. }
+        | Message::Command { .. }
+        | Message::Resume { .. } => {}
+    }
+}
+
+/// The next message from the server; never resolves while there is no client.
+async fn next_message(client: &mut Option<ChatClient>) -> Option<Message> {
+    match client {
+        Some(c) => c.rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Capture size: fixed by --video-width/--video-height, otherwise the larger of
+/// our own gallery tile and the largest size any participant asked for.
+fn capture_size(args: &Args, app: &App) -> (u16, u16) {
+    let mut size = app.local_tile_size();
+    for (w, h) in app.users().iter().filter_map(|u| u.preferred_size) {
+        size = (size.0.max(w), size.1.max(h));
+    }
+    let requested = requested_video_size(args);
+    (
+        requested.0.unwrap_or(size.0.min(MAX_VIDEO_SIZE.0)),
+        requested.1.unwrap_or(size.1.min(MAX_VIDEO_SIZE.1)),
+    )
+}
+
+/// `--video-width`/`--video-height`, held to `MAX_VIDEO_SIZE`.
+fn requested_video_size(args: &Args) -> (Option<u16>, Option<u16>) {
+    (
+        args.video_width.map(|w| w.min(MAX_VIDEO_SIZE.0)),
+        args.video_height.map(|h| h.min(MAX_VIDEO_SIZE.1)),
+    )
+}
+
 async fn setup_ngrok(port: u16) -> Result<String> {
     // For ngrok 0.14, we need to use it differently
     // This is a placeholder - ngrok integration would need proper setup
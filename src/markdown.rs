@@ -0,0 +1,113 @@
+use ratatui::{
+    style::{Modifier, Style},
+    text::Span,
+};
+
+use crate::theme::Theme;
+
+/// Styled spans for the supported markdown subset: `**bold**`, `_italic_`,
+/// `` `code` ``, ``` fenced code blocks ``` and bare links. Line breaks stay in
+/// the text as `\n` for the chat wrapper to handle.
+pub fn render(text: &str, theme: &Theme) -> Vec<Span<'static>> {
+    let code = Style::default().fg(theme.code);
+    let mut spans = Vec::new();
+    let mut in_block = false;
+    let mut first = true;
+
+    for line in text.split('\n') {
+        // Fence lines toggle the block and aren't shown
+        if line.trim_start().starts_with("```") {
+            in_block = !in_block;
+            continue;
+        }
+        // Code blocks always start below the sender's name
+        if !first || in_block {
+            spans.push(Span::raw("\n"));
+        }
+        first = false;
+
+        if in_block {
+            spans.push(Span::styled(format!("  {}", line), code));
+        } else {
+            inline(line, theme, &mut spans);
+        }
+    }
+    spans
+}
+
+/// Inline markup within one line. Markers without a partner are kept as text.
+fn inline(line: &str, theme: &Theme, spans: &mut Vec<Span<'static>>) {
+    let mut bold = false;
+    let mut italic = false;
+    let mut plain = String::new();
+    let style = |bold: bool, italic: bool| {
+        let mut style = Style::default();
+        if bold {
+            style = style.add_modifier(Modifier::BOLD);
+        }
+        if italic {
+            style = style.add_modifier(Modifier::ITALIC);
+        }
+        style
+    };
+    let flush = |plain: &mut String, spans: &mut Vec<Span<'static>>, current: Style| {
+        if !plain.is_empty() {
+            spans.push(Span::styled(std::mem::take(plain), current));
+        }
+    };
+
+    let mut i = 0;
+    while i < line.len() {
+        let rest = &line[i..];
+        let prev = line[..i].chars().next_back();
+        let current = style(bold, italic);
+
+        if let Some(end) = rest.strip_prefix('`').and_then(|r| r.find('`')) {
+            flush(&mut plain, spans, current);
+            spans.push(Span::styled(rest[1..1 + end].to_string(), Style::default().fg(theme.code)));
+            i += end + 2;
+            continue;
+        }
+        if rest.starts_with("**") && (bold || rest[2..].contains("**")) {
+            flush(&mut plain, spans, current);
+            bold = !bold;
+            i += 2;
+            continue;
+        }
+        if let Some(after) = rest.strip_prefix('_') {
+            // Only at word edges, so snake_case names stay intact
+            let next = after.chars().next();
+            let opens = !italic
+                && prev.is_none_or(|c| !c.is_alphanumeric())
+                && next.is_some_and(|c| !c.is_whitespace())
+                && after.contains('_');
+            let closes = italic
+                && prev.is_some_and(|c| !c.is_whitespace())
+                && next.is_none_or(|c| !c.is_alphanumeric());
+            if opens || closes {
+                flush(&mut plain, spans, current);
+                italic = !italic;
+                i += 1;
+                continue;
+            }
+        }
+        if (rest.starts_with("http://") || rest.starts_with("https://"))
+            && prev.is_none_or(|c| c.is_whitespace() || c == '(')
+        {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let url = rest[..end].trim_end_matches(['.', ',', ')', '!', '?']);
+            flush(&mut plain, spans, current);
+            spans.push(Span::styled(
+                url.to_string(),
+                current.fg(theme.link).add_modifier(Modifier::UNDERLINED),
+            ));
+            i += url.len();
+            continue;
+        }
+
+        let ch = rest.chars().next().unwrap();
+        plain.push(ch);
+        i += ch.len_utf8();
+    }
+    flush(&mut plain, spans, style(bold, italic));
+}
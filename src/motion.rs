@@ -0,0 +1,59 @@
+use std::time::{Duration, Instant};
+
+use crate::ascii::AsciiFrame;
+
+pub enum FrameDecision {
+    Send,
+    Keepalive,
+    Skip,
+}
+
+/// Decides whether a captured frame is worth sending, based on how much it
+/// differs from the last frame that went out.
+pub struct MotionGate {
+    threshold: f32,
+    keepalive: Duration,
+    reference: Option<AsciiFrame>,
+    last_sent: Option<Instant>,
+    pub motion: f32,
+}
+
+impl MotionGate {
+    pub fn new(threshold: f32, keepalive: Duration) -> Self {
+        Self {
+            threshold,
+            keepalive,
+            reference: None,
+            last_sent: None,
+            motion: 1.0,
+        }
+    }
+
+    pub fn check(&mut self, frame: &AsciiFrame) -> FrameDecision {
+        let now = Instant::now();
+
+        // Compare against the last *sent* frame so slow drift still adds up
+        self.motion = match &self.reference {
+            Some(previous) => frame.change_ratio(previous),
+            None => 1.0,
+        };
+
+        if self.motion >= self.threshold {
+            self.reference = Some(frame.clone());
+            self.last_sent = Some(now);
+            return FrameDecision::Send;
+        }
+
+        match self.last_sent {
+            Some(at) if now.duration_since(at) < self.keepalive => FrameDecision::Skip,
+            _ => {
+                self.last_sent = Some(now);
+                FrameDecision::Keepalive
+            }
+        }
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.motion >= self.threshold
+    }
+}
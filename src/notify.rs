@@ -0,0 +1,107 @@
+use crossterm::{queue, style::Print, terminal::SetTitle};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+pub const APP_TITLE: &str = "Terminal Chat";
+
+/// Ways to get the user's attention; terminals ignore the ones they don't support.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NotifyMethod {
+    /// Terminal bell (BEL)
+    Bell,
+    /// Desktop notification via OSC 9 (iTerm2, Windows Terminal, kitty, WezTerm)
+    Osc9,
+    /// Desktop notification via OSC 777 (rxvt, foot, Konsole, VTE-based terminals)
+    Osc777,
+    /// Window title change until the next key press
+    Title,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NotifyEvent {
+    Mention,
+    Direct,
+    Join,
+}
+
+/// Which methods fire for each kind of event.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationSettings {
+    pub mention: Vec<NotifyMethod>,
+    pub direct: Vec<NotifyMethod>,
+    pub join: Vec<NotifyMethod>,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            mention: vec![NotifyMethod::Bell, NotifyMethod::Title],
+            direct: vec![NotifyMethod::Bell, NotifyMethod::Title],
+            join: Vec::new(),
+        }
+    }
+}
+
+impl NotificationSettings {
+    pub fn methods(&self, event: NotifyEvent) -> &[NotifyMethod] {
+        match event {
+            NotifyEvent::Mention => &self.mention,
+            NotifyEvent::Direct => &self.direct,
+            NotifyEvent::Join => &self.join,
+        }
+    }
+}
+
+pub struct Notification {
+    pub event: NotifyEvent,
+    pub title: String,
+    pub body: String,
+}
+
+/// Writes the escape sequences for `notification`. Returns whether the window title changed.
+pub fn emit(out: &mut impl Write, methods: &[NotifyMethod], notification: &Notification) -> std::io::Result<bool> {
+    // Control characters would end the sequence early; `;` separates OSC 777 fields
+    let clean = |s: &str| s.chars().filter(|c| !c.is_control()).collect::<String>();
+    let title = clean(&notification.title);
+    let body = clean(&notification.body);
+    let mut title_changed = false;
+
+    for method in methods {
+        match method {
+            NotifyMethod::Bell => queue!(out, Print("\x07"))?,
+            NotifyMethod::Osc9 => queue!(out, Print(format!("\x1b]9;{}: {}\x07", title, body)))?,
+            NotifyMethod::Osc777 => queue!(
+                out,
+                Print(format!("\x1b]777;notify;{};{}\x07", title.replace(';', ","), body.replace(';', ",")))
+            )?,
+            NotifyMethod::Title => {
+                queue!(out, SetTitle(format!("* {} — {}", title, APP_TITLE)))?;
+                title_changed = true;
+            }
+        }
+    }
+    out.flush()?;
+    Ok(title_changed)
+}
+
+pub fn reset_title(out: &mut impl Write) -> std::io::Result<()> {
+    queue!(out, SetTitle(APP_TITLE))?;
+    out.flush()
+}
+
+/// Whether `text` mentions `name`, as `@name` or the bare name as a whole word.
+pub fn mentions(text: &str, name: &str) -> bool {
+    if name.is_empty() {
+        return false;
+    }
+    let lower = text.to_lowercase();
+    let name = name.to_lowercase();
+    lower.match_indices(&name).any(|(start, _)| {
+        let before = lower[..start].chars().next_back();
+        let after = lower[start + name.len()..].chars().next();
+        before.is_none_or(|c| !c.is_alphanumeric() && c != '_')
+            && after.is_none_or(|c| !c.is_alphanumeric() && c != '_')
+    })
+}
@@ -0,0 +1,121 @@
+use clap::ValueEnum;
+
+use crate::ascii::AsciiFrame;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OverlayPosition {
+    TopLeft,
+    Top,
+    TopRight,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+#[derive(Clone, Debug)]
+pub enum OverlayContent {
+    NameTag,
+    Clock,
+    Caption(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct Overlay {
+    pub content: OverlayContent,
+    pub position: OverlayPosition,
+    pub color: (u8, u8, u8),
+}
+
+/// Text stamped into outgoing frames after ASCII conversion, so it travels
+/// with the video as plain characters.
+#[derive(Clone, Debug, Default)]
+pub struct OverlayStage {
+    pub overlays: Vec<Overlay>,
+    pub username: Option<String>,
+}
+
+impl OverlayStage {
+    pub fn add(&mut self, content: OverlayContent, position: OverlayPosition, color: (u8, u8, u8)) {
+        self.overlays.push(Overlay { content, position, color });
+    }
+
+    pub fn set_caption(&mut self, caption: Option<String>, position: OverlayPosition, color: (u8, u8, u8)) {
+        self.overlays.retain(|o| !matches!(o.content, OverlayContent::Caption(_)));
+        if let Some(text) = caption {
+            self.add(OverlayContent::Caption(text), position, color);
+        }
+    }
+
+    pub fn apply(&self, frame: &mut AsciiFrame) {
+        for overlay in &self.overlays {
+            let text = match &overlay.content {
+                OverlayContent::NameTag => match &self.username {
+                    Some(name) => name.clone(),
+                    None => continue,
+                },
+                OverlayContent::Clock => chrono::Local::now().format("%H:%M:%S").to_string(),
+                OverlayContent::Caption(text) => text.clone(),
+            };
+            stamp(frame, &text, overlay.position, overlay.color);
+        }
+    }
+}
+
+fn stamp(frame: &mut AsciiFrame, text: &str, position: OverlayPosition, color: (u8, u8, u8)) {
+    if frame.width == 0 || frame.height == 0 {
+        return;
+    }
+
+    // Frames are serialized one byte per cell, so keep overlays to printable ASCII
+    let text: String = text
+        .chars()
+        .map(|c| if c.is_ascii_graphic() || c == ' ' { c } else { '?' })
+        .take(frame.width as usize)
+        .collect();
+    let len = text.len() as u16;
+
+    let x = match position {
+        OverlayPosition::TopLeft | OverlayPosition::BottomLeft => 0,
+        OverlayPosition::Top | OverlayPosition::Bottom => (frame.width - len) / 2,
+        OverlayPosition::TopRight | OverlayPosition::BottomRight => frame.width - len,
+    };
+    let y = match position {
+        OverlayPosition::TopLeft | OverlayPosition::Top | OverlayPosition::TopRight => 0,
+        _ => frame.height - 1,
+    };
+
+    frame.put_str(x, y, &text, color);
+}
+
+pub fn parse_hex_color(s: &str) -> Result<(u8, u8, u8), String> {
+    let hex = s.trim_start_matches('#');
+    if hex.len() != 6 {
+        return Err(format!("expected RRGGBB, got '{}'", s));
+    }
+    // Checked before slicing by byte, which would panic inside a multi-byte char
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(format!("invalid hex color '{}'", s));
+    }
+    let channel = |i: usize| {
+        u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| format!("invalid hex color '{}'", s))
+    };
+    Ok((channel(0)?, channel(2)?, channel(4)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hex_colors() {
+        assert_eq!(parse_hex_color("#ff8000"), Ok((255, 128, 0)));
+        assert_eq!(parse_hex_color("00FFaa"), Ok((0, 255, 170)));
+    }
+
+    #[test]
+    fn rejects_malformed_colors() {
+        for bad in ["", "#fff", "#ff80000", "#gg0000", "#ééé", "+12345"] {
+            assert!(parse_hex_color(bad).is_err(), "{}", bad);
+        }
+    }
+}
@@ -7,20 +7,62 @@ pub enum Message {
     Join {
         id: Uuid,
         username: String,
+        /// The client's long-lived id, kept across sessions (see `UserInfo::identity`)
+        #[serde(default)]
+        identity: Option<Uuid>,
     },
     Leave {
         id: Uuid,
     },
+    /// Sent instead of `Join` after a reconnect, to pick up the session `token` names
+    Resume {
+        token: String,
+        /// Used to join afresh if the session has expired
+        username: String,
+        #[serde(default)]
+        identity: Option<Uuid>,
+        /// `Sequenced::seq` of the last replayable message received; replay starts after it
+        #[serde(default)]
+        last_seq: Option<u64>,
+    },
+    /// Reply to `Join` and `Resume`: your id, and the token to resume with if the connection drops
+    Session {
+        id: Uuid,
+        token: String,
+        /// An earlier session was restored and the messages missed in between replayed
+        resumed: bool,
+    },
     Chat {
         id: Uuid,
         username: String,
         text: String,
         timestamp: u64,
+        /// Picked by the sender and echoed back, so it can tell its own message was delivered
+        #[serde(default)]
+        message_id: Option<Uuid>,
     },
     VideoFrame {
         id: Uuid,
         username: String,
         frame: Vec<u8>, // Serialized AsciiFrame
+        #[serde(default)]
+        layer: VideoLayer,
+        /// Capture time in ms on the sender's clock, for playout pacing; 0 plays on arrival
+        #[serde(default)]
+        timestamp_ms: u64,
+        /// Longest the sender goes between frames while its picture is static; 0 if it doesn't say
+        #[serde(default)]
+        keepalive_ms: u64,
+    },
+    /// Size a client would like remote video delivered at (its tile size)
+    PreferredSize {
+        width: u16,
+        height: u16,
+    },
+    /// Sent by a viewer to pick which simulcast layer of `id` it receives
+    SubscribeLayer {
+        id: Uuid,
+        layer: VideoLayer,
     },
     UserList {
         users: Vec<UserInfo>,
@@ -28,17 +70,139 @@ pub enum Message {
     ServerInfo {
         ngrok_url: Option<String>,
         room_name: String,
+        #[serde(default)]
+        topic: Option<String>,
+    },
+    /// Slash command the server should run, e.g. `/nick` (name without the slash)
+    Command {
+        name: String,
+        args: String,
+    },
+    /// Commands the server understands, advertised on connect
+    CommandList {
+        commands: Vec<CommandInfo>,
+    },
+    /// Server text meant for one client (command replies, notices)
+    Notice {
+        text: String,
+    },
+    /// `/me` emote
+    Action {
+        id: Uuid,
+        username: String,
+        text: String,
+        timestamp: u64,
+    },
+    /// `/msg` private message, delivered to the recipient and echoed to the sender
+    Direct {
+        from: Uuid,
+        from_name: String,
+        to: Uuid,
+        to_name: String,
+        text: String,
+        timestamp: u64,
+    },
+    Topic {
+        topic: String,
+        set_by: String,
     },
     Error {
         message: String,
     },
 }
 
+/// A message on its way to a client. Replayable broadcasts carry their position in
+/// the server's history; the field is simply ignored by clients that parse `Message`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sequenced {
+    #[serde(flatten)]
+    pub message: Message,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u64>,
+}
+
+impl From<Message> for Sequenced {
+    fn from(message: Message) -> Self {
+        Self { message, seq: None }
+    }
+}
+
+/// Senders publish every frame as a full-size and a reduced layer; the server
+/// forwards one of them per viewer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum VideoLayer {
+    Small,
+    #[default]
+    Large,
+}
+
+// Small layer dimensions are the large ones divided by this
+pub const SMALL_LAYER_DIVISOR: u16 = 2;
+
+impl VideoLayer {
+    /// Layer a viewer should ask for, given a received frame and the tile it is drawn in.
+    pub fn fit(self, frame_width: u16, frame_height: u16, tile: (u16, u16)) -> Self {
+        match self {
+            VideoLayer::Large if frame_width > tile.0 || frame_height > tile.1 => VideoLayer::Small,
+            // Sizes come from a peer's frame, so don't trust them not to overflow
+            VideoLayer::Small
+                if frame_width.saturating_mul(SMALL_LAYER_DIVISOR) <= tile.0
+                    && frame_height.saturating_mul(SMALL_LAYER_DIVISOR) <= tile.1 =>
+            {
+                VideoLayer::Large
+            }
+            layer => layer,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandInfo {
+    pub name: String,
+    pub usage: String,
+    pub help: String,
+    #[serde(default)]
+    pub arg: ArgKind,
+}
+
+impl CommandInfo {
+    /// Builds the advertised list from a static (name, usage, help, argument) table.
+    pub fn from_table(table: &[(&str, &str, &str, ArgKind)]) -> Vec<Self> {
+        table
+            .iter()
+            .map(|&(name, usage, help, arg)| CommandInfo {
+                name: name.to_string(),
+                usage: usage.to_string(),
+                help: help.to_string(),
+                arg,
+            })
+            .collect()
+    }
+}
+
+/// What a command's first argument is, so clients can complete it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ArgKind {
+    #[default]
+    None,
+    User,
+    Text,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserInfo {
     pub id: Uuid,
     pub username: String,
     pub joined_at: u64,
+    #[serde(default)]
+    pub preferred_size: Option<(u16, u16)>,
+    /// Round trip time between the server and this user, from WebSocket pings
+    #[serde(default)]
+    pub rtt_ms: Option<u32>,
+    /// Stays the same across sessions and renames, unlike `id`; chosen by the client,
+    /// so only good for local preferences such as ignoring someone
+    #[serde(default)]
+    pub identity: Option<Uuid>,
 }
 
 impl Message {
@@ -50,4 +214,17 @@ impl Message {
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, serde_json::Error> {
         serde_json::from_slice(bytes)
     }
+
+    /// Kept in the server's history and replayed to a client that resumes.
+    pub fn replayable(&self) -> bool {
+        matches!(
+            self,
+            Message::Chat { .. }
+                | Message::Action { .. }
+                | Message::Notice { .. }
+                | Message::Topic { .. }
+                | Message::Join { .. }
+                | Message::Leave { .. }
+        )
+    }
 }
\ No newline at end of file
@@ -10,24 +10,111 @@ use axum::{
 };
 use futures_util::{SinkExt, StreamExt};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     net::SocketAddr,
-    sync::Arc,
-    time::{SystemTime, UNIX_EPOCH},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
-use tokio::sync::{broadcast, RwLock};
+use tokio::sync::{broadcast, mpsc, RwLock};
 use tower_http::cors::CorsLayer;
 use uuid::Uuid;
 
-use crate::protocol::{Message, UserInfo};
+use crate::protocol::{ArgKind, CommandInfo, Message, Sequenced, UserInfo, VideoLayer};
 
 type Users = Arc<RwLock<HashMap<Uuid, UserInfo>>>;
+/// Per-connection channels for messages meant for one client only
+type Peers = Arc<RwLock<HashMap<Uuid, mpsc::UnboundedSender<Sequenced>>>>;
+/// Joined users by resume token
+type Sessions = Arc<RwLock<HashMap<String, Session>>>;
+
+const MAX_NAME_LENGTH: usize = 24;
+
+// How often each connection is pinged, and how often the resulting round trip times are shared
+const PING_INTERVAL: Duration = Duration::from_secs(5);
+const USER_LIST_INTERVAL: Duration = Duration::from_secs(10);
+// A connection that hasn't answered a few pings is gone, even if the socket never said so
+const IDLE_TIMEOUT: Duration = Duration::from_secs(15);
+
+// How long a dropped user stays in the room waiting to resume, and how much chat is kept to replay to them
+const RESUME_GRACE: Duration = Duration::from_secs(60);
+const HISTORY_SIZE: usize = 200;
+
+/// Commands the server handles, advertised to clients on connect: (name, usage, help, argument).
+const SERVER_COMMANDS: &[(&str, &str, &str, ArgKind)] = &[
+    ("nick", "/nick <name>", "Change your display name", ArgKind::Text),
+    ("me", "/me <action>", "Describe what you are doing", ArgKind::Text),
+    ("msg", "/msg <user> <text>", "Send a private message", ArgKind::User),
+    ("topic", "/topic [text]", "Show or set the room topic", ArgKind::Text),
+    ("who", "/who", "List who is in the room", ArgKind::None),
+];
+
+fn server_commands() -> Vec<CommandInfo> {
+    CommandInfo::from_table(SERVER_COMMANDS)
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+struct Session {
+    id: Uuid,
+    /// Bumped on every resume, so a superseded connection doesn't clean up after the new one
+    generation: u64,
+    /// History position when the connection dropped; `None` while connected
+    left_at: Option<u64>,
+    /// History position up to which the current connection has been written to
+    delivered: Arc<AtomicU64>,
+}
+
+/// Recent broadcasts worth replaying, numbered in the order they were sent.
+#[derive(Default)]
+struct History {
+    next_seq: u64,
+    messages: VecDeque<(u64, Message)>,
+}
+
+impl History {
+    fn since(&self, seq: u64) -> impl Iterator<Item = Sequenced> + '_ {
+        self.messages
+            .iter()
+            .filter(move |(s, _)| *s >= seq)
+            .map(|(s, m)| Sequenced { message: m.clone(), seq: Some(*s) })
+    }
+}
+
+/// The connection's user after `Join` or `Resume`.
+struct Joined {
+    id: Uuid,
+    username: String,
+    token: String,
+    generation: u64,
+    /// Where to replay history from, when resuming
+    replay_from: Option<u64>,
+    delivered: Arc<AtomicU64>,
+}
 
 #[derive(Clone)]
 pub struct ServerState {
     pub users: Users,
-    pub tx: broadcast::Sender<Message>,
+    pub tx: broadcast::Sender<Sequenced>,
     pub ngrok_url: Arc<RwLock<Option<String>>>,
+    pub peers: Peers,
+    pub topic: Arc<RwLock<Option<String>>>,
+    sessions: Sessions,
+    history: Arc<RwLock<History>>,
 }
 
 impl ServerState {
@@ -37,6 +124,126 @@ impl ServerState {
             users: Arc::new(RwLock::new(HashMap::new())),
             tx,
             ngrok_url: Arc::new(RwLock::new(None)),
+            peers: Arc::new(RwLock::new(HashMap::new())),
+            topic: Arc::new(RwLock::new(None)),
+            sessions: Arc::new(RwLock::new(HashMap::new())),
+            history: Arc::new(RwLock::new(History::default())),
+        }
+    }
+
+    /// Sends `message` to everyone, keeping chat and room events for sessions that resume later.
+    pub async fn broadcast(&self, message: Message) {
+        if !message.replayable() {
+            let _ = self.tx.send(message.into());
+            return;
+        }
+        
+        // Sent under the lock so a resuming connection sees each message exactly once
+        let mut history = self.history.write().await;
+        let seq = history.next_seq;
+        history.next_seq += 1;
+        history.messages.push_back((seq, message.clone()));
+        if history.messages.len() > HISTORY_SIZE {
+            history.messages.pop_front();
+        }
+        let _ = self.tx.send(Sequenced { message, seq: Some(seq) });
+    }
+
+    /// Our broadcast of `user_id`'s chat `message_id`, if it's still in history.
+    async fn sent_chat(&self, user_id: Uuid, message_id: Uuid) -> Option<Message> {
+        let history = self.history.read().await;
+        history
+            .messages
+            .iter()
+            .rev()
+            .map(|(_, message)| message)
+            .find(|message| {
+                matches!(message, Message::Chat { id, message_id: Some(m), .. } if *id == user_id && *m == message_id)
+            })
+            .cloned()
+    }
+
+    /// Restores the session `token` names, or joins `username` as a new user. A
+    /// resuming client's `last_seq` says which history it already has.
+    async fn join(&self, token: Option<&str>, username: String, identity: Option<Uuid>, last_seq: Option<u64>) -> Joined {
+        if let Some(token) = token {
+            if let Some(session) = self.sessions.write().await.get_mut(token) {
+                session.generation += 1;
+                // The old socket may not have timed out yet; then go by what was written to it
+                let left_at = session.left_at.take().unwrap_or_else(|| session.delivered.load(Ordering::Relaxed));
+                let replay_from = last_seq.map_or(left_at, |seq| seq + 1);
+                session.delivered = Arc::new(AtomicU64::new(replay_from));
+                let username = self
+                    .users
+                    .read()
+                    .await
+                    .get(&session.id)
+                    .map_or(username, |u| u.username.clone());
+                return Joined {
+                    id: session.id,
+                    username,
+                    token: token.to_string(),
+                    generation: session.generation,
+                    replay_from: Some(replay_from),
+                    delivered: session.delivered.clone(),
+                };
+            }
+        }
+        
+        let id = Uuid::new_v4();
+        let token = Uuid::new_v4().simple().to_string();
+        let delivered = Arc::new(AtomicU64::new(self.history.read().await.next_seq));
+        let session = Session { id, generation: 0, left_at: None, delivered: delivered.clone() };
+        self.sessions.write().await.insert(token.clone(), session);
+        let user_info = UserInfo {
+            id,
+            username: username.clone(),
+            joined_at: now_secs(),
+            preferred_size: None,
+            rtt_ms: None,
+            identity,
+        };
+        self.users.write().await.insert(id, user_info);
+        
+        self.broadcast(Message::Join { id, username: username.clone(), identity }).await;
+        let users: Vec<UserInfo> = self.users.read().await.values().cloned().collect();
+        self.broadcast(Message::UserList { users }).await;
+        Joined { id, username, token, generation: 0, replay_from: None, delivered }
+    }
+
+    /// Called when a connection ends. A client that closed the socket has left; one that
+    /// dropped stays listed for `RESUME_GRACE`, then leaves unless it resumed in the meantime.
+    async fn disconnect(&self, joined: &Joined, closed: bool) {
+        // Broadcasts that never made it onto the socket are replayed on resume
+        let left_at = joined.delivered.load(Ordering::Relaxed);
+        match self.sessions.write().await.get_mut(&joined.token) {
+            Some(session) if session.generation == joined.generation => session.left_at = Some(left_at),
+            // A newer connection has taken over
+            _ => return,
+        }
+        self.peers.write().await.remove(&joined.id);
+        if closed {
+            self.leave(&joined.token, joined.generation).await;
+            return;
+        }
+        
+        let state = self.clone();
+        let token = joined.token.clone();
+        let generation = joined.generation;
+        tokio::spawn(async move {
+            tokio::time::sleep(RESUME_GRACE).await;
+            state.leave(&token, generation).await;
+        });
+    }
+
+    /// Ends session `token` and tells everyone, unless a newer connection has resumed it.
+    async fn leave(&self, token: &str, generation: u64) {
+        let mut sessions = self.sessions.write().await;
+        if sessions.get(token).is_some_and(|s| s.generation == generation && s.left_at.is_some()) {
+            let id = sessions.remove(token).map(|s| s.id).unwrap();
+            drop(sessions);
+            self.users.write().await.remove(&id);
+            self.broadcast(Message::Leave { id }).await;
         }
     }
 
@@ -46,6 +253,19 @@ impl ServerState {
 }
 
 pub async fn start_server(state: ServerState, port: u16) -> Result<()> {
+    // Refresh everyone's view of round trip times
+    let list_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(USER_LIST_INTERVAL);
+        loop {
+            interval.tick().await;
+            let users: Vec<UserInfo> = list_state.users.read().await.values().cloned().collect();
+            if !users.is_empty() {
+                let _ = list_state.tx.send(Message::UserList { users }.into());
+            }
+        }
+    });
+    
     let app = Router::new()
         .route("/ws", get(websocket_handler))
         .with_state(state)
@@ -74,74 +294,135 @@ async fn websocket_handler(
 
 async fn handle_socket(socket: WebSocket, state: ServerState) {
     let (mut sender, mut receiver) = socket.split();
-    let mut rx = state.tx.subscribe();
-    let user_id = Uuid::new_v4();
-    let mut username = String::new();
-
+    
     // Send server info
-    if let Ok(msg) = serde_json::to_string(&Message::ServerInfo {
-        ngrok_url: state.ngrok_url.read().await.clone(),
-        room_name: "Terminal Chat Room".to_string(),
-    }) {
-        let _ = sender.send(WsMessage::Text(msg)).await;
+    for message in [
+        Message::ServerInfo {
+            ngrok_url: state.ngrok_url.read().await.clone(),
+            room_name: "Terminal Chat Room".to_string(),
+            topic: state.topic.read().await.clone(),
+        },
+        Message::CommandList { commands: server_commands() },
+    ] {
+        if let Ok(msg) = serde_json::to_string(&message) {
+            let _ = sender.send(WsMessage::Text(msg)).await;
+        }
     }
+    
+    // Nothing else happens until the client joins or resumes
+    let joined = loop {
+        let Some(Ok(msg)) = receiver.next().await else {
+            return;
+        };
+        let WsMessage::Text(text) = msg else {
+            continue;
+        };
+        match Message::from_bytes(text.as_bytes()) {
+            Ok(Message::Join { username, identity, .. }) => break state.join(None, username, identity, None).await,
+            Ok(Message::Resume { token, username, identity, last_seq }) => {
+                break state.join(Some(&token), username, identity, last_seq).await
+            }
+            _ => {}
+        }
+    };
+    let user_id = joined.id;
+    let mut username = joined.username.clone();
+    
+    let (direct_tx, mut direct_rx) = mpsc::unbounded_channel();
+    state.peers.write().await.insert(user_id, direct_tx.clone());
+    let _ = direct_tx.send(Message::Session {
+        id: user_id,
+        token: joined.token.clone(),
+        resumed: joined.replay_from.is_some(),
+    }.into());
+    
+    // Subscribe and collect what was missed under the same lock, so nothing falls in between
+    let mut rx = {
+        let history = state.history.read().await;
+        if let Some(seq) = joined.replay_from {
+            for message in history.since(seq) {
+                let _ = direct_tx.send(message);
+            }
+        }
+        state.tx.subscribe()
+    };
+    let users: Vec<UserInfo> = state.users.read().await.values().cloned().collect();
+    let _ = direct_tx.send(Message::UserList { users }.into());
+    
+    // Simulcast layer this viewer wants from each sender (Large unless asked otherwise)
+    let layers: Arc<RwLock<HashMap<Uuid, VideoLayer>>> = Arc::new(RwLock::new(HashMap::new()));
+    let recv_layers = layers.clone();
 
     // Handle incoming messages
     let state_clone = state.clone();
     let recv_task = tokio::spawn(async move {
-        while let Some(msg) = receiver.next().await {
+        // Pongs count too, so this only trips once the client has stopped answering
+        while let Ok(Some(msg)) = tokio::time::timeout(IDLE_TIMEOUT, receiver.next()).await {
             if let Ok(msg) = msg {
+                // Said goodbye, rather than just going quiet
+                if let WsMessage::Close(_) = msg {
+                    return true;
+                }
+                // Pong payload is the send time of our ping
+                if let WsMessage::Pong(data) = &msg {
+                    if let Ok(sent) = <[u8; 8]>::try_from(data.as_slice()) {
+                        let rtt = now_millis().saturating_sub(u64::from_be_bytes(sent));
+                        if let Some(user) = state_clone.users.write().await.get_mut(&user_id) {
+                            user.rtt_ms = Some(rtt.min(u32::MAX as u64) as u32);
+                        }
+                    }
+                    continue;
+                }
                 if let WsMessage::Text(text) = msg {
                     if let Ok(message) = Message::from_bytes(text.as_bytes()) {
                         match message {
-                            Message::Join { username: name, .. } => {
-                                username = name.clone();
-                                
-                                // Add user to list
-                                let user_info = UserInfo {
-                                    id: user_id,
-                                    username: username.clone(),
-                                    joined_at: SystemTime::now()
-                                        .duration_since(UNIX_EPOCH)
-                                        .unwrap()
-                                        .as_secs(),
+                            Message::Chat { text, message_id, .. } => {
+                                // Clients resend what they didn't see echoed before reconnecting;
+                                // if it got through, only the sender hears about it again
+                                let earlier = match message_id {
+                                    Some(message_id) => state_clone.sent_chat(user_id, message_id).await,
+                                    None => None,
                                 };
-                                
-                                state_clone.users.write().await.insert(user_id, user_info.clone());
-                                
-                                // Broadcast join message
-                                let _ = state_clone.tx.send(Message::Join {
+                                match earlier {
+                                    Some(earlier) => {
+                                        let _ = direct_tx.send(earlier.into());
+                                    }
+                                    None => {
+                                        state_clone.broadcast(Message::Chat {
+                                            id: user_id,
+                                            username: username.clone(),
+                                            text,
+                                            timestamp: now_secs(),
+                                            message_id,
+                                        }).await;
+                                    }
+                                }
+                            }
+                            Message::VideoFrame { frame, layer, timestamp_ms, keepalive_ms, .. } => {
+                                let _ = state_clone.tx.send(Message::VideoFrame {
                                     id: user_id,
                                     username: username.clone(),
-                                });
-                                
-                                // Send user list to new user
-                                let users: Vec<UserInfo> = state_clone.users.read().await
-                                    .values()
-                                    .cloned()
-                                    .collect();
-                                let _ = state_clone.tx.send(Message::UserList { users });
+                                    frame,
+                                    layer,
+                                    timestamp_ms,
+                                    keepalive_ms,
+                                }.into());
                             }
-                            Message::Chat { text, .. } => {
-                                if !username.is_empty() {
-                                    let _ = state_clone.tx.send(Message::Chat {
-                                        id: user_id,
-                                        username: username.clone(),
-                                        text,
-                                        timestamp: SystemTime::now()
-                                            .duration_since(UNIX_EPOCH)
-                                            .unwrap()
-                                            .as_secs(),
-                                    });
+                            Message::PreferredSize { width, height } => {
+                                let mut users = state_clone.users.write().await;
+                                if let Some(user) = users.get_mut(&user_id) {
+                                    user.preferred_size = Some((width, height));
+                                    let users: Vec<UserInfo> = users.values().cloned().collect();
+                                    let _ = state_clone.tx.send(Message::UserList { users }.into());
                                 }
                             }
-                            Message::VideoFrame { frame, .. } => {
-                                if !username.is_empty() {
-                                    let _ = state_clone.tx.send(Message::VideoFrame {
-                                        id: user_id,
-                                        username: username.clone(),
-                                        frame,
-                                    });
+                            Message::SubscribeLayer { id, layer } => {
+                                recv_layers.write().await.insert(id, layer);
+                            }
+                            Message::Command { name, args } => {
+                                let reply = run_command(&state_clone, user_id, &mut username, &name, &args).await;
+                                if let Some(reply) = reply {
+                                    let _ = direct_tx.send(reply.into());
                                 }
                             }
                             _ => {}
@@ -150,26 +431,175 @@ async fn handle_socket(socket: WebSocket, state: ServerState) {
                 }
             }
         }
+        false
     });
 
     // Broadcast messages to this client
+    let delivered = joined.delivered.clone();
     let send_task = tokio::spawn(async move {
-        while let Ok(msg) = rx.recv().await {
+        let mut ping = tokio::time::interval(PING_INTERVAL);
+        loop {
+            // Direct messages first, so a resumed session's replay comes before anything new
+            let msg = tokio::select! {
+                biased;
+                _ = ping.tick() => {
+                    let sent = now_millis().to_be_bytes().to_vec();
+                    if sender.send(WsMessage::Ping(sent)).await.is_err() {
+                        break;
+                    }
+                    continue;
+                }
+                Some(msg) = direct_rx.recv() => msg,
+                msg = rx.recv() => match msg {
+                    Ok(msg) => msg,
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+            };
+            
+            if let Message::VideoFrame { id, layer, .. } = &msg.message {
+                // Senders already show their own preview
+                if *id == user_id {
+                    continue;
+                }
+                let wanted = layers.read().await.get(id).copied().unwrap_or_default();
+                if *layer != wanted {
+                    continue;
+                }
+            }
+            
             if let Ok(text) = serde_json::to_string(&msg) {
                 if sender.send(WsMessage::Text(text)).await.is_err() {
                     break;
                 }
+                if let Some(seq) = msg.seq {
+                    delivered.fetch_max(seq + 1, Ordering::Relaxed);
+                }
             }
         }
     });
 
     // Wait for tasks to complete
-    tokio::select! {
-        _ = recv_task => {},
-        _ = send_task => {},
-    }
+    let recv_abort = recv_task.abort_handle();
+    let send_abort = send_task.abort_handle();
+    let closed = tokio::select! {
+        closed = recv_task => closed.unwrap_or(false),
+        _ = send_task => false,
+    };
+    recv_abort.abort();
+    send_abort.abort();
+
+    state.disconnect(&joined, closed).await;
+}
+
+/// Runs a slash command for `user_id`. Results for everyone are broadcast; the
+/// returned message, if any, goes back to the caller only.
+async fn run_command(
+    state: &ServerState,
+    user_id: Uuid,
+    username: &mut String,
+    name: &str,
+    args: &str,
+) -> Option<Message> {
+    let error = |message: &str| Some(Message::Error { message: message.to_string() });
 
-    // Clean up on disconnect
-    state.users.write().await.remove(&user_id);
-    let _ = state.tx.send(Message::Leave { id: user_id });
-}
\ No newline at end of file
+    match name {
+        "nick" => {
+            let new_name = args.trim();
+            if new_name.is_empty() || new_name.chars().count() > MAX_NAME_LENGTH {
+                return error(&format!("Names must be 1-{} characters", MAX_NAME_LENGTH));
+            }
+            if new_name.chars().any(char::is_whitespace) {
+                return error("Names cannot contain spaces");
+            }
+            
+            let mut users = state.users.write().await;
+            if users.values().any(|u| u.id != user_id && u.username.eq_ignore_ascii_case(new_name)) {
+                return error(&format!("{} is already taken", new_name));
+            }
+            if let Some(user) = users.get_mut(&user_id) {
+                user.username = new_name.to_string();
+            }
+            let old_name = std::mem::replace(username, new_name.to_string());
+            let list: Vec<UserInfo> = users.values().cloned().collect();
+            drop(users);
+            
+            state.broadcast(Message::Notice {
+                text: format!("{} is now known as {}", old_name, new_name),
+            }).await;
+            state.broadcast(Message::UserList { users: list }).await;
+            None
+        }
+        "me" => {
+            if args.is_empty() {
+                return error("Usage: /me <action>");
+            }
+            state.broadcast(Message::Action {
+                id: user_id,
+                username: username.clone(),
+                text: args.to_string(),
+                timestamp: now_secs(),
+            }).await;
+            None
+        }
+        "msg" => {
+            let Some((to_name, text)) = args.split_once(char::is_whitespace) else {
+                return error("Usage: /msg <user> <text>");
+            };
+            let Some(to) = state
+                .users
+                .read()
+                .await
+                .values()
+                .find(|u| u.username.eq_ignore_ascii_case(to_name))
+                .cloned()
+            else {
+                return error(&format!("No user named {}", to_name));
+            };
+            
+            let direct = Message::Direct {
+                from: user_id,
+                from_name: username.clone(),
+                to: to.id,
+                to_name: to.username,
+                text: text.trim().to_string(),
+                timestamp: now_secs(),
+            };
+            if to.id != user_id {
+                if let Some(peer) = state.peers.read().await.get(&to.id) {
+                    let _ = peer.send(direct.clone().into());
+                }
+            }
+            Some(direct)
+        }
+        "topic" => {
+            if args.is_empty() {
+                let text = match state.topic.read().await.as_deref() {
+                    Some(topic) => format!("Topic: {}", topic),
+                    None => "No topic is set".to_string(),
+                };
+                return Some(Message::Notice { text });
+            }
+            *state.topic.write().await = Some(args.to_string());
+            state.broadcast(Message::Topic {
+                topic: args.to_string(),
+                set_by: username.clone(),
+            }).await;
+            None
+        }
+        "who" => {
+            let mut names: Vec<String> = state
+                .users
+                .read()
+                .await
+                .values()
+                .map(|u| u.username.clone())
+                .collect();
+            names.sort_by_key(|n| n.to_lowercase());
+            Some(Message::Notice {
+                text: format!("{} in the room: {}", names.len(), names.join(", ")),
+            })
+        }
+        _ => error(&format!("Unknown command /{}", name)),
+    }
+}
@@ -0,0 +1,299 @@
+use anyhow::{Context, Result};
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use uuid::Uuid;
+
+use crate::config::ClientConfig;
+
+// WCAG contrast ratio user colors must reach against the background
+const MIN_CONTRAST: f64 = 4.5;
+
+pub const BUILTIN_THEMES: &[&str] = &["default", "light", "high-contrast", "monochrome", "solarized"];
+
+/// Colors for every panel and role in the UI. Theme files only need the keys
+/// they change; the rest come from the default theme.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    /// Fills the whole screen; `reset` keeps the terminal's own background
+    pub background: Color,
+    /// Outer border and app title
+    pub frame: Color,
+    /// Headings such as the welcome title
+    pub title: Color,
+    pub text: Color,
+    /// Hints and placeholders
+    pub dim: Color,
+    /// Username entry box
+    pub accent: Color,
+    pub chat_border: Color,
+    /// Message input and search prompt
+    pub input_border: Color,
+    pub users_border: Color,
+    /// Video panel and your own tile
+    pub video_border: Color,
+    /// Other participants' tiles
+    pub tile_border: Color,
+    /// Selected user and their tile
+    pub selected: Color,
+    /// Search matches and the chosen completion
+    pub highlight_fg: Color,
+    pub highlight_bg: Color,
+    /// Inline code and code blocks in messages
+    pub code: Color,
+    pub link: Color,
+    /// Messages that mention you
+    pub mention: Color,
+    /// Messages that failed to send
+    pub error: Color,
+    /// Give every user their own name color; when false names use `text`
+    pub user_colors: bool,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            background: Color::Reset,
+            frame: Color::Cyan,
+            title: Color::Yellow,
+            text: Color::White,
+            dim: Color::Gray,
+            accent: Color::Green,
+            chat_border: Color::Green,
+            input_border: Color::Yellow,
+            users_border: Color::Cyan,
+            video_border: Color::Magenta,
+            tile_border: Color::Cyan,
+            selected: Color::Yellow,
+            highlight_fg: Color::Black,
+            highlight_bg: Color::Yellow,
+            code: Color::LightYellow,
+            link: Color::LightBlue,
+            mention: Color::LightMagenta,
+            error: Color::Red,
+            user_colors: true,
+        }
+    }
+}
+
+impl Theme {
+    pub fn builtin(name: &str) -> Option<Self> {
+        let theme = match name {
+            "default" => Self::default(),
+            // For terminals with a light background
+            "light" => Self {
+                frame: Color::Blue,
+                title: Color::Magenta,
+                text: Color::Black,
+                dim: Color::DarkGray,
+                accent: Color::Blue,
+                input_border: Color::Magenta,
+                users_border: Color::Blue,
+                tile_border: Color::Blue,
+                selected: Color::Red,
+                highlight_fg: Color::White,
+                highlight_bg: Color::Blue,
+                code: Color::Magenta,
+                link: Color::Blue,
+                mention: Color::Red,
+                error: Color::Red,
+                ..Self::default()
+            },
+            "high-contrast" => Self {
+                background: Color::Black,
+                frame: Color::White,
+                title: Color::LightYellow,
+                text: Color::White,
+                dim: Color::White,
+                accent: Color::LightCyan,
+                chat_border: Color::White,
+                input_border: Color::LightYellow,
+                users_border: Color::White,
+                video_border: Color::White,
+                tile_border: Color::White,
+                selected: Color::LightYellow,
+                highlight_fg: Color::Black,
+                highlight_bg: Color::LightYellow,
+                code: Color::LightCyan,
+                link: Color::LightCyan,
+                mention: Color::LightMagenta,
+                error: Color::LightRed,
+                user_colors: true,
+            },
+            "monochrome" => Self {
+                background: Color::Reset,
+                frame: Color::Gray,
+                title: Color::White,
+                text: Color::Reset,
+                dim: Color::DarkGray,
+                accent: Color::White,
+                chat_border: Color::Gray,
+                input_border: Color::White,
+                users_border: Color::Gray,
+                video_border: Color::Gray,
+                tile_border: Color::DarkGray,
+                selected: Color::White,
+                highlight_fg: Color::Black,
+                highlight_bg: Color::White,
+                code: Color::Gray,
+                link: Color::White,
+                mention: Color::White,
+                error: Color::White,
+                user_colors: false,
+            },
+            // Solarized dark
+            "solarized" => Self {
+                background: Color::Rgb(0x00, 0x2b, 0x36),
+                frame: Color::Rgb(0x2a, 0xa1, 0x98),
+                title: Color::Rgb(0xb5, 0x89, 0x00),
+                text: Color::Rgb(0x83, 0x94, 0x96),
+                dim: Color::Rgb(0x58, 0x6e, 0x75),
+                accent: Color::Rgb(0x85, 0x99, 0x00),
+                chat_border: Color::Rgb(0x85, 0x99, 0x00),
+                input_border: Color::Rgb(0xb5, 0x89, 0x00),
+                users_border: Color::Rgb(0x26, 0x8b, 0xd2),
+                video_border: Color::Rgb(0xd3, 0x36, 0x82),
+                tile_border: Color::Rgb(0x2a, 0xa1, 0x98),
+                selected: Color::Rgb(0xcb, 0x4b, 0x16),
+                highlight_fg: Color::Rgb(0x00, 0x2b, 0x36),
+                highlight_bg: Color::Rgb(0xb5, 0x89, 0x00),
+                code: Color::Rgb(0x2a, 0xa1, 0x98),
+                link: Color::Rgb(0x6c, 0x71, 0xc4),
+                mention: Color::Rgb(0xdc, 0x32, 0x2f),
+                error: Color::Rgb(0xdc, 0x32, 0x2f),
+                user_colors: true,
+            },
+            _ => return None,
+        };
+        Some(theme)
+    }
+
+    /// A stable color for `id`: its hue comes from the id, and its lightness is
+    /// pushed away from the background until the name is comfortably readable.
+    pub fn user_color(&self, id: Uuid) -> Color {
+        if !self.user_colors {
+            return self.text;
+        }
+        
+        let hue = (id.as_u128() % 360) as f64;
+        let background = self.background_rgb();
+        let step = if luminance(background) < 0.5 { 0.05 } else { -0.05 };
+        let mut lightness = 0.6;
+        loop {
+            let rgb = hsl_to_rgb(hue, 0.7, lightness);
+            let done = !(0.1..=0.9).contains(&(lightness + step));
+            if contrast(rgb, background) >= MIN_CONTRAST || done {
+                return Color::Rgb(rgb.0, rgb.1, rgb.2);
+            }
+            lightness += step;
+        }
+    }
+
+    /// Best guess at the background; with the terminal's own, assume it contrasts with `text`.
+    fn background_rgb(&self) -> (u8, u8, u8) {
+        match self.background {
+            Color::Reset if luminance(to_rgb(self.text)) < 0.5 => (255, 255, 255),
+            Color::Reset => (0, 0, 0),
+            color => to_rgb(color),
+        }
+    }
+
+    /// A built-in theme, `<config dir>/terminal-chat/themes/<name>.toml`, or a path to a `.toml` file.
+    pub fn load(name: &str) -> Result<Self> {
+        if let Some(theme) = Self::builtin(name) {
+            return Ok(theme);
+        }
+
+        let path = if name.ends_with(".toml") {
+            PathBuf::from(name)
+        } else {
+            ClientConfig::dir()
+                .context("No config directory on this platform")?
+                .join("themes")
+                .join(format!("{}.toml", name))
+        };
+        let text = std::fs::read_to_string(&path).with_context(|| {
+            format!(
+                "Unknown theme {} (built-in: {}; or create {})",
+                name,
+                BUILTIN_THEMES.join(", "),
+                path.display()
+            )
+        })?;
+        toml::from_str(&text).with_context(|| format!("Invalid theme in {}", path.display()))
+    }
+}
+
+/// Approximate RGB for a terminal color (xterm defaults for the named ones).
+fn to_rgb(color: Color) -> (u8, u8, u8) {
+    const ANSI: [(u8, u8, u8); 16] = [
+        (0, 0, 0), (205, 0, 0), (0, 205, 0), (205, 205, 0),
+        (0, 0, 238), (205, 0, 205), (0, 205, 205), (229, 229, 229),
+        (127, 127, 127), (255, 0, 0), (0, 255, 0), (255, 255, 0),
+        (92, 92, 255), (255, 0, 255), (0, 255, 255), (255, 255, 255),
+    ];
+    let index = match color {
+        Color::Rgb(r, g, b) => return (r, g, b),
+        Color::Indexed(i) => i,
+        Color::Black => 0,
+        Color::Red => 1,
+        Color::Green => 2,
+        Color::Yellow => 3,
+        Color::Blue => 4,
+        Color::Magenta => 5,
+        Color::Cyan => 6,
+        Color::Gray => 7,
+        Color::DarkGray => 8,
+        Color::LightRed => 9,
+        Color::LightGreen => 10,
+        Color::LightYellow => 11,
+        Color::LightBlue => 12,
+        Color::LightMagenta => 13,
+        Color::LightCyan => 14,
+        Color::White | Color::Reset => 15,
+    };
+    match index {
+        0..=15 => ANSI[index as usize],
+        // 6x6x6 color cube
+        16..=231 => {
+            let level = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+            let i = index - 16;
+            (level(i / 36), level(i / 6 % 6), level(i % 6))
+        }
+        _ => {
+            let gray = 8 + (index - 232) * 10;
+            (gray, gray, gray)
+        }
+    }
+}
+
+/// WCAG relative luminance, 0 (black) to 1 (white).
+fn luminance((r, g, b): (u8, u8, u8)) -> f64 {
+    let channel = |c: u8| {
+        let c = c as f64 / 255.0;
+        if c <= 0.03928 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+    };
+    0.2126 * channel(r) + 0.7152 * channel(g) + 0.0722 * channel(b)
+}
+
+fn contrast(a: (u8, u8, u8), b: (u8, u8, u8)) -> f64 {
+    let (la, lb) = (luminance(a), luminance(b));
+    (la.max(lb) + 0.05) / (la.min(lb) + 0.05)
+}
+
+fn hsl_to_rgb(hue: f64, saturation: f64, lightness: f64) -> (u8, u8, u8) {
+    let c = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let x = c * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let m = lightness - c / 2.0;
+    let (r, g, b) = match hue as u32 / 60 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let byte = |v: f64| ((v + m) * 255.0).round() as u8;
+    (byte(r), byte(g), byte(b))
+}
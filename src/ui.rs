@@ -1,138 +1,1156 @@
 use anyhow::Result;
-use crossterm::event::KeyCode;
+use serde::{Deserialize, Serialize};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
 use ratatui::{
-    layout::{Alignment, Constraint, Direction, Layout, Margin, Rect},
+    layout::{Alignment, Constraint, Direction, Layout, Margin, Position, Rect},
     prelude::*,
     style::{Color, Modifier, Style},
-    widgets::{Block, Borders, List, ListItem, Paragraph, Wrap},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
 };
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
 use tachyonfx::{fx, EffectManager, Interpolation};
+use unicode_width::UnicodeWidthChar;
+use uuid::Uuid;
 
+use crate::adaptive::QualityLevel;
 use crate::ascii::AsciiFrame;
-use crate::protocol::UserInfo;
+use crate::client::ConnectionStatus;
+use crate::commands::{self, LocalCommand, ParsedCommand, QueueCommand};
+use crate::completion::Completion;
+use crate::config::{ClientConfig, IgnoredUser};
+use crate::editor::LineEditor;
+use crate::jitter::JitterBuffer;
+use crate::keymap::{Action, Keymap, Preset};
+use crate::markdown;
+use crate::notify::{self, Notification, NotifyEvent};
+use crate::protocol::{CommandInfo, UserInfo};
+use crate::theme::Theme;
 
+// Smallest gallery tile (including its border) before tiles spill onto another page
+const MIN_TILE_WIDTH: u16 = 14;
+const MIN_TILE_HEIGHT: u16 = 6;
+// Filmstrip row height in spotlight mode, and thumbnail column width in chat-focus mode
+const FILMSTRIP_HEIGHT: u16 = 8;
+const THUMBNAIL_WIDTH: u16 = 22;
+// Input box stops growing (and scrolls) past this many lines
+const MAX_INPUT_LINES: usize = 5;
+// Chat lines moved per mouse wheel notch
+const WHEEL_LINES: usize = 3;
+// Mentions remembered for /mentions
+const MAX_MENTIONS: usize = 50;
+// Keepalive interval assumed for senders that don't announce theirs (the `--keepalive-secs` default)
+const DEFAULT_KEEPALIVE: Duration = Duration::from_secs(2);
+// How much later than its keepalive a sender's next frame may be before its tile is
+// shown frozen, and before it counts as camera off
+const STALE_VIDEO: Duration = Duration::from_secs(1);
+const VIDEO_TIMEOUT: Duration = Duration::from_secs(3);
+// Name and details line per entry in the users panel
+const USER_ROW_HEIGHT: u16 = 2;
+// Below this nothing useful fits, even with tabs
+const MIN_WIDTH: u16 = 32;
+const MIN_HEIGHT: u16 = 10;
+// Our own message counts as lost if the server hasn't echoed it back by then
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LayoutMode {
+    #[default]
+    Gallery,
+    Spotlight,
+    ChatFocus,
+    VideoOnly,
+}
+
+impl LayoutMode {
+    pub fn next(self) -> Self {
+        match self {
+            LayoutMode::Gallery => LayoutMode::Spotlight,
+            LayoutMode::Spotlight => LayoutMode::ChatFocus,
+            LayoutMode::ChatFocus => LayoutMode::VideoOnly,
+            LayoutMode::VideoOnly => LayoutMode::Gallery,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            LayoutMode::Gallery => "gallery",
+            LayoutMode::Spotlight => "spotlight",
+            LayoutMode::ChatFocus => "chat",
+            LayoutMode::VideoOnly => "video",
+        }
+    }
+}
+
+/// Panel shown in the tabbed layout used on small terminals.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Tab {
+    #[default]
+    Chat,
+    Video,
+    Users,
+}
+
+impl Tab {
+    pub const ALL: [Tab; 3] = [Tab::Chat, Tab::Video, Tab::Users];
+
+    pub fn next(self) -> Self {
+        match self {
+            Tab::Chat => Tab::Video,
+            Tab::Video => Tab::Users,
+            Tab::Users => Tab::Chat,
+        }
+    }
+}
+
+#[allow(clippy::large_enum_variant)]
 pub enum AppState {
     UsernameEntry(String),
     Chat {
-        _username: String,
-        input_buffer: String,
+        username: String,
+        input: LineEditor,
         messages: VecDeque<ChatMessage>,
         users: Vec<UserInfo>,
         video_frame: Option<AsciiFrame>,
-        remote_frames: Vec<(String, AsciiFrame)>,
+        remote_frames: Vec<RemoteVideo>,
+        /// Chat lines hidden below the bottom of the panel (0 = following new messages)
+        scroll: usize,
+        /// Messages that arrived while scrolled up
+        unseen: usize,
+        search: Option<Search>,
+        completion: Option<Completion>,
     },
 }
 
+pub struct Search {
+    pub query: String,
+    /// Index into `messages` of the match currently shown
+    pub current: Option<usize>,
+}
+
+pub struct RemoteVideo {
+    pub id: Uuid,
+    pub username: String,
+    pub frame: AsciiFrame,
+    pub updated: Instant,
+}
+
+/// Whether a remote tile is still getting frames.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamState {
+    Live,
+    /// Still in the room, but their frames stopped
+    Paused,
+    /// They're gone from the room, or we've lost the connection
+    Disconnected,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MessageKind {
+    Chat,
+    /// `/me` emote
+    Action,
+    /// `/msg`; `username` reads "from → to"
+    Private,
+}
+
+/// How far one of our own chat messages has got.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Delivery {
+    /// In the outbox, waiting for the connection
+    Queued,
+    /// Sent as `id`; waiting for the server to echo it back
+    Pending { id: Uuid, since: Instant },
+    Delivered,
+    /// No echo within `DELIVERY_TIMEOUT`; a late one still counts
+    Failed { id: Uuid },
+}
+
+/// Chat waiting in the outbox. The id is chosen once, so a resend after a
+/// reconnect is recognised by the server as the same message.
+pub struct Outgoing {
+    pub message_id: Uuid,
+    pub text: String,
+}
+
+impl Outgoing {
+    pub fn new(text: String) -> Self {
+        Self { message_id: Uuid::new_v4(), text }
+    }
+}
+
 pub struct ChatMessage {
+    pub kind: MessageKind,
+    /// Who sent it; `None` for local and server notices
+    pub sender: Option<Uuid>,
+    /// Names you, and is drawn highlighted
+    pub mention: bool,
     pub username: String,
     pub text: String,
     pub timestamp: String,
+    /// Set on messages we sent, shown before the server has them
+    pub delivery: Option<Delivery>,
+}
+
+/// Areas from the last draw, used to size video, bound paging and hit-test the mouse.
+#[derive(Clone, Default)]
+pub struct ChatLayout {
+    /// Message area of the chat panel and the number of wrapped lines in it
+    pub chat: Rect,
+    pub chat_lines: usize,
+    /// Message index and text of each visible chat row, from `chat_top` down
+    pub chat_rows: Vec<(usize, String)>,
+    pub chat_top: u16,
+    /// Every video tile on screen, border included; `None` is the local preview
+    pub tiles: Vec<(Option<Uuid>, Rect)>,
+    pub gallery_pages: usize,
+    pub video: Rect,
+    /// Users list area and the index of its first visible row
+    pub users: Rect,
+    pub users_offset: usize,
+    /// Whole column area and the x of the chat and users panels, when their edges can be dragged
+    pub columns: Rect,
+    pub borders: Option<(u16, u16)>,
+    /// Drawn as tabs because the terminal is small, and where each tab title is
+    pub compact: bool,
+    pub tabs: Vec<(Tab, Rect)>,
+}
+
+impl ChatLayout {
+    fn tile_size(&self, id: Option<Uuid>) -> (u16, u16) {
+        self.tiles
+            .iter()
+            .find(|(tile_id, _)| *tile_id == id)
+            .map(|(_, area)| inner_size(*area))
+            .unwrap_or((0, 0))
+    }
+}
+
+fn inner_size(tile: Rect) -> (u16, u16) {
+    let inner = tile.inner(Margin::new(1, 1));
+    (inner.width, inner.height)
+}
+
+/// Column edge being dragged with the mouse.
+#[derive(Clone, Copy)]
+enum PanelBorder {
+    Video,
+    Chat,
+}
+
+/// Popup drawn over the chat that takes all keys while open.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Dialog {
+    Help { scroll: u16 },
+    ConfirmQuit,
+}
+
+#[derive(Clone, Copy)]
+enum TileSource {
+    Local,
+    Remote(usize),
 }
 
 pub struct App {
     pub state: AppState,
     pub effects: EffectManager<()>,
+    /// When the start-up effects finish; until then the screen is redrawn every frame
+    effects_until: Instant,
     pub should_quit: bool,
     pub ngrok_url: Option<String>,
+    pub motion: bool,
+    pub quality: Option<QualityLevel>,
+    pub layout: ChatLayout,
+    pub gallery_page: usize,
+    pub config: ClientConfig,
+    pub theme: Theme,
+    pub keymap: Keymap,
+    pub dialog: Option<Dialog>,
+    /// Vi preset only: keys are motions instead of text
+    pub vi_normal: bool,
+    /// Tabbed layout: the panel shown, and chat messages that arrived while it was another
+    pub tab: Tab,
+    pub tab_unread: usize,
+    pub selected_user: Option<Uuid>,
+    pub active_speaker: Option<Uuid>,
+    /// Local commands plus those advertised by the server
+    pub commands: Vec<CommandInfo>,
+    pub topic: Option<String>,
+    drag: Option<PanelBorder>,
+    /// Our id as assigned by the server when we joined
+    pub own_id: Option<Uuid>,
+    pub connection: ConnectionStatus,
+    /// Recent mentions, oldest first, and how many arrived since you last sent something
+    pub mentions: VecDeque<String>,
+    pub unread_mentions: usize,
+    /// Waiting to be written to the terminal
+    pub notifications: Vec<Notification>,
+    /// Chat typed while disconnected, oldest first, sent in order once the connection is back
+    pub outbox: VecDeque<Outgoing>,
+    /// Remote frames held back until their playout time, per sender
+    pub jitter: HashMap<Uuid, JitterBuffer>,
+    /// Messages from ignored users that weren't shown, by identity
+    pub hidden: HashMap<Uuid, usize>,
+    /// Keepalive interval each sender announces with its frames
+    keepalive: HashMap<Uuid, Duration>,
 }
 
 impl App {
-    pub fn new() -> Self {
+    pub fn new(config: ClientConfig, theme: Theme, keymap: Keymap) -> Self {
         let mut effects = EffectManager::default();
+        let settings = &config.effects;
         
-        // Boot effect with CRT-style fade in
-        let boot = fx::sequence(&[
-            fx::fade_from(Color::Black, Color::Reset, (500, Interpolation::Linear)),
-            fx::coalesce(300),
-        ]);
-        
-        // Subtle color shift for retro feel
-        let drift = fx::hsl_shift(
-            Some([0.0, 0.0, 0.02]),
-            None,
-            (8000, Interpolation::SineInOut)
-        );
-        
-        effects.add_effect(fx::parallel(&[boot, drift]));
+        if settings.enabled {
+            let mut parts = Vec::new();
+            
+            // Boot effect with CRT-style fade in
+            let mut boot = Vec::new();
+            if settings.fade_ms > 0 {
+                let from = if theme.background == Color::Reset { Color::Black } else { theme.background };
+                boot.push(fx::fade_from(from, Color::Reset, (settings.fade_ms, Interpolation::Linear)));
+            }
+            if settings.coalesce_ms > 0 {
+                boot.push(fx::coalesce(settings.coalesce_ms));
+            }
+            if !boot.is_empty() {
+                parts.push(fx::sequence(&boot));
+            }
+            
+            // Subtle color shift for retro feel
+            if settings.drift_ms > 0 && settings.drift_amount != 0.0 {
+                parts.push(fx::hsl_shift(
+                    Some([0.0, 0.0, settings.drift_amount as f32]),
+                    None,
+                    (settings.drift_ms, Interpolation::SineInOut)
+                ));
+            }
+            
+            if !parts.is_empty() {
+                effects.add_effect(fx::parallel(&parts));
+            }
+        }
+        let effects_ms = if settings.enabled {
+            (settings.fade_ms + settings.coalesce_ms).max(settings.drift_ms)
+        } else {
+            0
+        };
         
         Self {
             state: AppState::UsernameEntry(String::new()),
             effects,
+            effects_until: Instant::now() + Duration::from_millis(effects_ms as u64),
             should_quit: false,
             ngrok_url: None,
+            motion: false,
+            quality: None,
+            layout: ChatLayout::default(),
+            gallery_page: 0,
+            config,
+            theme,
+            keymap,
+            dialog: None,
+            vi_normal: false,
+            tab: Tab::Chat,
+            tab_unread: 0,
+            selected_user: None,
+            active_speaker: None,
+            commands: commands::local_commands(),
+            topic: None,
+            drag: None,
+            own_id: None,
+            connection: ConnectionStatus::Offline,
+            mentions: VecDeque::new(),
+            unread_mentions: 0,
+            notifications: Vec::new(),
+            outbox: VecDeque::new(),
+            jitter: HashMap::new(),
+            keepalive: HashMap::new(),
+            hidden: HashMap::new(),
         }
     }
 
-    pub fn handle_key(&mut self, key: KeyCode) -> Result<Option<UserAction>> {
-        match &mut self.state {
-            AppState::UsernameEntry(buffer) => {
-                match key {
-                    KeyCode::Enter => {
-                        if !buffer.is_empty() {
-                            let username = buffer.clone();
-                            self.state = AppState::Chat {
-                                _username: username.clone(),
-                                input_buffer: String::new(),
-                                messages: VecDeque::new(),
-                                users: Vec::new(),
-                                video_frame: None,
-                                remote_frames: Vec::new(),
-                            };
-                            return Ok(Some(UserAction::JoinChat(username)));
-                        }
-                    }
-                    KeyCode::Backspace => {
-                        buffer.pop();
-                    }
-                    KeyCode::Char(c) => {
-                        if buffer.len() < 20 {
-                            buffer.push(c);
+    pub fn handle_key(&mut self, key: KeyEvent) -> Result<Option<UserAction>> {
+        if self.dialog.is_some() {
+            self.handle_dialog_key(key);
+            return Ok(None);
+        }
+        
+        if let AppState::UsernameEntry(buffer) = &mut self.state {
+            match key.code {
+                KeyCode::Enter => {
+                    if !buffer.is_empty() {
+                        let username = buffer.clone();
+                        self.state = AppState::Chat {
+                            username: username.clone(),
+                            input: LineEditor::default(),
+                            messages: VecDeque::new(),
+                            users: Vec::new(),
+                            video_frame: None,
+                            remote_frames: Vec::new(),
+                            scroll: 0,
+                            unseen: 0,
+                            search: None,
+                            completion: None,
+                        };
+                        if let Some(error) = self.config.load_error.clone() {
+                            self.add_message("Error".to_string(), format!("Using default settings: {}", error));
                         }
+                        return Ok(Some(UserAction::JoinChat(username)));
                     }
-                    KeyCode::Esc => {
-                        self.should_quit = true;
+                }
+                KeyCode::Backspace => {
+                    buffer.pop();
+                }
+                KeyCode::Char(c) => {
+                    if buffer.len() < 20 {
+                        buffer.push(c);
                     }
-                    _ => {}
                 }
+                KeyCode::Esc => {
+                    self.should_quit = true;
+                }
+                _ => {}
             }
-            AppState::Chat { input_buffer, .. } => {
-                match key {
-                    KeyCode::Enter => {
-                        if !input_buffer.is_empty() {
-                            let msg = input_buffer.clone();
-                            input_buffer.clear();
-                            return Ok(Some(UserAction::SendMessage(msg)));
-                        }
-                    }
-                    KeyCode::Backspace => {
-                        input_buffer.pop();
-                    }
-                    KeyCode::Char(c) => {
-                        input_buffer.push(c);
-                    }
-                    KeyCode::Esc => {
-                        self.should_quit = true;
+            return Ok(None);
+        }
+        
+        if let AppState::Chat { search: Some(_), .. } = &self.state {
+            self.handle_search_key(key.code);
+            return Ok(None);
+        }
+        let AppState::Chat { input, completion, .. } = &mut self.state else {
+            return Ok(None);
+        };
+        
+        let action = self.keymap.lookup(key, self.vi_normal, input.is_empty());
+        if !matches!(action, Some(Action::Complete | Action::CompleteBack)) {
+            *completion = None;
+        }
+        match action {
+            Some(action) => Ok(self.run_action(action)),
+            None => {
+                // Anything unbound types, except in vi normal mode. Unbound Ctrl or Alt chords
+                // don't, but both together do: that's how AltGr arrives on Windows
+                let chord = key.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT);
+                let plain = chord != KeyModifiers::CONTROL && chord != KeyModifiers::ALT;
+                if let (KeyCode::Char(c), true, false) = (key.code, plain, self.vi_normal) {
+                    input.insert_char(c);
+                    // Don't type into a hidden input
+                    if self.layout.compact {
+                        self.select_tab(Tab::Chat);
                     }
-                    _ => {}
                 }
+                Ok(None)
+            }
+        }
+    }
+
+    fn run_action(&mut self, action: Action) -> Option<UserAction> {
+        let page = (self.layout.chat.height as usize).saturating_sub(1).max(1);
+        match action {
+            Action::Help => self.dialog = Some(Dialog::Help { scroll: 0 }),
+            Action::Quit => self.request_quit(),
+            Action::NextTab => self.select_tab(self.tab.next()),
+            Action::CycleLayout => {
+                self.config.layout = self.config.layout.next();
+                self.save_config();
+            }
+            Action::PrevPage => {
+                let last = self.layout.gallery_pages.saturating_sub(1);
+                self.gallery_page = self.gallery_page.min(last).saturating_sub(1);
+            }
+            Action::NextPage if self.gallery_page + 1 < self.layout.gallery_pages => {
+                self.gallery_page += 1;
+            }
+            Action::Search => self.start_search(),
+            Action::ScrollUp => self.scroll_chat(true, page),
+            Action::ScrollDown => self.scroll_chat(false, page),
+            Action::ScrollTop => self.scroll_chat(true, usize::MAX),
+            Action::ScrollBottom => self.scroll_chat(false, usize::MAX),
+            Action::ShrinkVideo => self.resize_video(-5),
+            Action::GrowVideo => self.resize_video(5),
+            Action::SelectPrev => self.select_user(false),
+            Action::SelectNext => self.select_user(true),
+            Action::ToggleIgnore => {
+                if let Some(id) = self.selected_user {
+                    let ignored = self.ignored_identity(id).is_some();
+                    self.set_ignored(id, !ignored);
+                }
+            }
+            Action::Complete => self.complete(true),
+            Action::CompleteBack => self.complete(false),
+            _ => return self.edit(action),
+        }
+        None
+    }
+
+    /// Actions on the message input, including vi mode switches.
+    fn edit(&mut self, action: Action) -> Option<UserAction> {
+        let AppState::Chat { input, .. } = &mut self.state else {
+            return None;
+        };
+        
+        match action {
+            Action::Submit => {
+                if !input.is_empty() {
+                    let text = input.submit();
+                    return self.submit_input(text);
+                }
+            }
+            Action::Newline => input.insert_char('\n'),
+            Action::CursorLeft => input.move_left(),
+            Action::CursorRight => input.move_right(),
+            Action::WordLeft => input.move_word_left(),
+            Action::WordRight => input.move_word_right(),
+            // With nothing to move through, Home/End jump through chat history
+            Action::LineStart if input.is_empty() => self.scroll_chat(true, usize::MAX),
+            Action::LineEnd if input.is_empty() => self.scroll_chat(false, usize::MAX),
+            Action::LineStart => input.move_home(),
+            Action::LineEnd => input.move_end(),
+            Action::DeleteBack => input.backspace(),
+            Action::DeleteForward => input.delete(),
+            Action::DeleteWord => input.delete_word_before(),
+            Action::DeleteToStart => input.delete_to_line_start(),
+            Action::DeleteToEnd => input.delete_to_line_end(),
+            Action::HistoryPrev => input.up(),
+            Action::HistoryNext => input.down(),
+            Action::NormalMode => self.vi_normal = true,
+            Action::Insert => self.vi_normal = false,
+            Action::Append => {
+                input.move_right();
+                self.vi_normal = false;
+            }
+            Action::AppendEnd => {
+                input.move_end();
+                self.vi_normal = false;
+            }
+            Action::InsertStart => {
+                input.move_home();
+                self.vi_normal = false;
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn select_tab(&mut self, tab: Tab) {
+        self.tab = tab;
+        if tab == Tab::Chat {
+            self.tab_unread = 0;
+        }
+    }
+
+    /// Whether the local preview is on screen, so a new camera frame needs a redraw.
+    pub fn shows_video(&self) -> bool {
+        matches!(self.state, AppState::Chat { .. }) && (!self.layout.compact || self.tab == Tab::Video)
+    }
+
+    /// Quits, or asks first when `confirm_quit` is set.
+    fn request_quit(&mut self) {
+        if self.config.confirm_quit {
+            self.dialog = Some(Dialog::ConfirmQuit);
+        } else {
+            self.should_quit = true;
+        }
+    }
+
+    /// The help overlay scrolls and closes with Esc, q, F1 or `?`; the quit
+    /// dialog takes y/Enter or n/Esc.
+    fn handle_dialog_key(&mut self, key: KeyEvent) {
+        match (&mut self.dialog, key.code) {
+            (Some(Dialog::Help { scroll }), KeyCode::Up | KeyCode::Char('k')) => *scroll = scroll.saturating_sub(1),
+            (Some(Dialog::Help { scroll }), KeyCode::Down | KeyCode::Char('j')) => *scroll += 1,
+            (Some(Dialog::Help { scroll }), KeyCode::PageUp) => *scroll = scroll.saturating_sub(10),
+            (Some(Dialog::Help { scroll }), KeyCode::PageDown) => *scroll += 10,
+            (Some(Dialog::Help { .. }), KeyCode::Esc | KeyCode::Enter | KeyCode::F(1) | KeyCode::Char('q' | '?')) => {
+                self.dialog = None;
+            }
+            (Some(Dialog::ConfirmQuit), KeyCode::Char('y' | 'Y')) => {
+                self.dialog = None;
+                self.should_quit = true;
+            }
+            // Enter right after sending a message shouldn't leave the chat
+            (Some(Dialog::ConfirmQuit), KeyCode::Char('n' | 'N') | KeyCode::Esc | KeyCode::Enter) => self.dialog = None,
+            _ => {}
+        }
+    }
+
+    /// Moves the video column edge by `step` percent, taking the space from (or giving it to) the chat column.
+    fn resize_video(&mut self, step: i16) {
+        let video = (self.config.video_percent as i16 + step).clamp(10, 70) as u16;
+        let chat = self.config.chat_percent as i16 - (video as i16 - self.config.video_percent as i16);
+        if chat >= 20 && video + chat as u16 <= 90 {
+            self.config.video_percent = video;
+            self.config.chat_percent = chat as u16;
+            self.save_config();
+        }
+    }
+
+    fn select_user(&mut self, forward: bool) {
+        let users = self.users();
+        if users.is_empty() {
+            return;
+        }
+        let current = self
+            .selected_user
+            .and_then(|id| users.iter().position(|u| u.id == id));
+        let next = match (current, forward) {
+            (None, false) => users.len() - 1,
+            (None, true) => 0,
+            (Some(i), false) => (i + users.len() - 1) % users.len(),
+            (Some(i), true) => (i + 1) % users.len(),
+        };
+        self.selected_user = Some(users[next].id);
+    }
+
+    /// Wheel scrolls chat (or pages the gallery under the pointer), left click
+    /// selects users, enlarges tiles and copies links, and dragging moves panel edges.
+    pub fn handle_mouse(&mut self, event: MouseEvent) -> Option<UserAction> {
+        if !matches!(self.state, AppState::Chat { .. }) || self.dialog.is_some() {
+            return None;
+        }
+        let position = Position::new(event.column, event.row);
+        
+        match event.kind {
+            MouseEventKind::ScrollUp | MouseEventKind::ScrollDown => {
+                let up = event.kind == MouseEventKind::ScrollUp;
+                if self.layout.gallery_pages > 1 && self.layout.video.contains(position) {
+                    let last = self.layout.gallery_pages - 1;
+                    self.gallery_page = if up {
+                        self.gallery_page.min(last).saturating_sub(1)
+                    } else {
+                        (self.gallery_page + 1).min(last)
+                    };
+                } else {
+                    self.scroll_chat(up, WHEEL_LINES);
+                }
+            }
+            MouseEventKind::Down(MouseButton::Left) => return self.handle_click(position),
+            MouseEventKind::Drag(MouseButton::Left) => {
+                if let Some(border) = self.drag {
+                    self.drag_border(border, event.column);
+                }
+            }
+            MouseEventKind::Up(MouseButton::Left) if self.drag.is_some() => {
+                self.drag = None;
+                self.save_config();
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn handle_click(&mut self, position: Position) -> Option<UserAction> {
+        if let Some(&(tab, _)) = self.layout.tabs.iter().find(|(_, area)| area.contains(position)) {
+            self.select_tab(tab);
+            return None;
+        }
+        
+        // Panel edges: the right border of one panel or the left border of the next
+        if let Some((chat_x, users_x)) = self.layout.borders {
+            let inside = self.layout.columns.contains(position);
+            let on = |x: u16| inside && (position.x + 1 == x || position.x == x);
+            if on(chat_x) {
+                self.drag = Some(PanelBorder::Video);
+                return None;
+            }
+            if on(users_x) {
+                self.drag = Some(PanelBorder::Chat);
+                return None;
+            }
+        }
+        
+        if self.layout.users.contains(position) {
+            let index = self.layout.users_offset + ((position.y - self.layout.users.y) / USER_ROW_HEIGHT) as usize;
+            if let Some(id) = self.users().get(index).map(|u| u.id) {
+                self.selected_user = if self.selected_user == Some(id) { None } else { Some(id) };
+            }
+            return None;
+        }
+        
+        if let Some(&(tile_id, _)) = self.layout.tiles.iter().find(|(_, area)| area.contains(position)) {
+            // Remote tiles go big in spotlight; clicking the spotlit tile goes back to the gallery
+            if let Some(id) = tile_id {
+                if self.config.layout == LayoutMode::Spotlight && self.selected_user == Some(id) {
+                    self.config.layout = LayoutMode::Gallery;
+                } else {
+                    self.selected_user = Some(id);
+                    self.config.layout = LayoutMode::Spotlight;
+                }
+                self.save_config();
+            }
+            return None;
+        }
+        
+        if self.layout.chat.contains(position) {
+            // Rows above a short history are empty
+            let row = position.y.checked_sub(self.layout.chat_top)? as usize;
+            let column = position.x - self.layout.chat.x;
+            let AppState::Chat { messages, .. } = &self.state else {
+                return None;
+            };
+            let (index, text) = self.layout.chat_rows.get(row)?;
+            let url = url_at(&messages.get(*index)?.text, text, column)?;
+            return Some(UserAction::CopyToClipboard(url));
+        }
+        None
+    }
+
+    /// Moves a column edge to `column`, keeping the panels within their size limits.
+    fn drag_border(&mut self, border: PanelBorder, column: u16) {
+        let area = self.layout.columns;
+        if area.width == 0 {
+            return;
+        }
+        let percent = (column.saturating_sub(area.x) as u32 * 100 / area.width as u32) as u16;
+        
+        match border {
+            PanelBorder::Video => {
+                // Keep the users panel the same size
+                let total = self.config.video_percent + self.config.chat_percent;
+                let video = percent.clamp(10, 70);
+                if total.saturating_sub(video) >= 20 {
+                    self.config.video_percent = video;
+                    self.config.chat_percent = total - video;
+                }
+            }
+            PanelBorder::Chat => {
+                // As drawn, so a hand-edited config can't make the bounds cross
+                let video = self.config.video_percent.clamp(10, 70);
+                self.config.chat_percent = percent.saturating_sub(video).clamp(20, 90 - video);
+            }
+        }
+    }
+
+    fn scroll_chat(&mut self, up: bool, lines: usize) {
+        let max_scroll = self.layout.chat_lines.saturating_sub(self.layout.chat.height as usize);
+        if let AppState::Chat { scroll, unseen, .. } = &mut self.state {
+            *scroll = if up {
+                (*scroll + lines).min(max_scroll)
+            } else {
+                scroll.saturating_sub(lines)
+            };
+            if *scroll == 0 {
+                *unseen = 0;
+            }
+        }
+    }
+
+    /// Sends plain text as chat; `/name args` runs a command (`//` sends a literal slash).
+    fn submit_input(&mut self, text: String) -> Option<UserAction> {
+        self.unread_mentions = 0;
+        let Some(line) = text.strip_prefix('/') else {
+            return Some(UserAction::SendMessage(text));
+        };
+        if line.starts_with('/') {
+            return Some(UserAction::SendMessage(line.to_string()));
+        }
+        
+        match commands::parse(line, &self.commands) {
+            ParsedCommand::Local(LocalCommand::Help) => {
+                let help: Vec<String> = self
+                    .commands
+                    .iter()
+                    .map(|c| format!("{:<20} {}", c.usage, c.help))
+                    .collect();
+                self.add_message("System".to_string(), format!("Commands:\n{}", help.join("\n")));
+                None
+            }
+            ParsedCommand::Local(LocalCommand::Clear) => {
+                if let AppState::Chat { messages, scroll, unseen, .. } = &mut self.state {
+                    messages.clear();
+                    *scroll = 0;
+                    *unseen = 0;
+                }
+                None
+            }
+            ParsedCommand::Local(LocalCommand::Mentions) => {
+                let text = if self.mentions.is_empty() {
+                    "No mentions yet".to_string()
+                } else {
+                    format!("Mentions:\n{}", Vec::from(self.mentions.clone()).join("\n"))
+                };
+                self.add_message("System".to_string(), text);
+                None
+            }
+            ParsedCommand::Local(LocalCommand::Caption(caption)) => Some(UserAction::SetCaption(caption)),
+            ParsedCommand::Local(LocalCommand::Queue(command)) => {
+                self.run_queue_command(command);
+                None
+            }
+            ParsedCommand::Local(LocalCommand::Ignore(None)) => {
+                self.list_ignored();
+                None
+            }
+            ParsedCommand::Local(LocalCommand::Ignore(Some(name))) => {
+                match self.find_user(&name) {
+                    Some(id) => self.set_ignored(id, true),
+                    None => self.add_message("Error".to_string(), format!("No user named {}", name)),
+                }
+                None
+            }
+            ParsedCommand::Local(LocalCommand::Unignore(None)) => {
+                self.add_message("Error".to_string(), "Usage: /unignore <user>".to_string());
+                None
+            }
+            ParsedCommand::Local(LocalCommand::Unignore(Some(name))) => {
+                self.unignore_by_name(&name);
+                None
+            }
+            ParsedCommand::Local(LocalCommand::Quit) => {
+                self.should_quit = true;
+                None
+            }
+            ParsedCommand::Server { name, args } => Some(UserAction::Command { name, args }),
+            ParsedCommand::Unknown(name) => {
+                self.add_message("Error".to_string(), format!("Unknown command /{} (try /help)", name));
+                None
             }
         }
-        Ok(None)
+    }
+
+    /// `/queue`: messages typed while offline can be listed, dropped, or taken back into the input.
+    fn run_queue_command(&mut self, command: QueueCommand) {
+        // 1-based, newest by default
+        let index = |n: Option<usize>, len: usize| match n {
+            None => len.checked_sub(1),
+            Some(n) => (n <= len).then(|| n - 1),
+        };
+        
+        match command {
+            QueueCommand::List if self.outbox.is_empty() => {
+                self.add_message("System".to_string(), "No messages waiting to be sent".to_string());
+            }
+            QueueCommand::List => {
+                let lines: Vec<String> = self
+                    .outbox
+                    .iter()
+                    .enumerate()
+                    .map(|(i, outgoing)| format!("{}. {}", i + 1, outgoing.text))
+                    .collect();
+                self.add_message("System".to_string(), format!("Waiting to be sent:\n{}", lines.join("\n")));
+            }
+            QueueCommand::Edit(_) if matches!(&self.state, AppState::Chat { input, .. } if !input.is_empty()) => {
+                self.add_message("Error".to_string(), "Send or clear what you're typing first".to_string());
+            }
+            QueueCommand::Cancel(n) | QueueCommand::Edit(n) => {
+                let Some(Outgoing { text, .. }) = index(n, self.outbox.len()).and_then(|i| self.outbox.remove(i)) else {
+                    self.add_message("Error".to_string(), "No such queued message (see /queue)".to_string());
+                    return;
+                };
+                if let (QueueCommand::Edit(_), AppState::Chat { input, .. }) = (command, &mut self.state) {
+                    input.set_text(&text);
+                }
+            }
+            QueueCommand::Clear => self.outbox.clear(),
+            QueueCommand::Invalid => {
+                self.add_message("Error".to_string(), "Usage: /queue [cancel [n]|edit [n]|clear]".to_string());
+            }
+        }
+    }
+
+    /// Merges the server's advertised commands with the local ones.
+    pub fn set_server_commands(&mut self, server: Vec<CommandInfo>) {
+        let mut all = commands::local_commands();
+        let server: Vec<CommandInfo> = server
+            .into_iter()
+            .filter(|c| !all.iter().any(|l| l.name == c.name))
+            .collect();
+        all.extend(server);
+        self.commands = all;
+    }
+
+    /// Tab/Shift+Tab: complete the word before the cursor, cycling on repeat.
+    fn complete(&mut self, forward: bool) {
+        let AppState::Chat { input, users, completion, .. } = &mut self.state else {
+            return;
+        };
+        
+        match completion {
+            Some(active) => active.cycle(forward),
+            None => {
+                *completion = Completion::new(input.before_cursor(), users, &self.commands);
+                if let (Some(active), false) = (completion.as_mut(), forward) {
+                    active.cycle(false);
+                }
+            }
+        }
+        if let Some(active) = completion {
+            input.replace_before_cursor(active.start, active.current());
+        }
+    }
+
+    fn start_search(&mut self) {
+        if let AppState::Chat { search, .. } = &mut self.state {
+            *search = Some(Search { query: String::new(), current: None });
+        }
+    }
+
+    /// While searching, typing edits the query, Up/Down step through older and
+    /// newer matches, Enter keeps the current position and Esc returns to the bottom.
+    fn handle_search_key(&mut self, key: KeyCode) {
+        let AppState::Chat { messages, search: Some(search), scroll, unseen, .. } = &mut self.state else {
+            return;
+        };
+        
+        let query = search.query.to_lowercase();
+        let matching = |i: usize| message_matches(&messages[i], &query);
+        let target = match key {
+            KeyCode::Char(c) => {
+                search.query.push(c);
+                let query = search.query.to_lowercase();
+                (0..messages.len()).rev().find(|&i| message_matches(&messages[i], &query))
+            }
+            KeyCode::Backspace => {
+                search.query.pop();
+                let query = search.query.to_lowercase();
+                (0..messages.len()).rev().find(|&i| message_matches(&messages[i], &query))
+            }
+            KeyCode::Up => {
+                let before = search.current.unwrap_or(messages.len());
+                (0..before).rev().find(|&i| matching(i)).or(search.current)
+            }
+            KeyCode::Down => {
+                let after = search.current.map_or(messages.len(), |i| i + 1);
+                (after..messages.len()).find(|&i| matching(i)).or(search.current)
+            }
+            KeyCode::Enter => {
+                self.set_search(None);
+                return;
+            }
+            KeyCode::Esc => {
+                *scroll = 0;
+                *unseen = 0;
+                self.set_search(None);
+                return;
+            }
+            _ => return,
+        };
+        
+        search.current = target;
+        if let Some(index) = target {
+            self.scroll_to_message(index);
+        }
+    }
+
+    fn set_search(&mut self, value: Option<Search>) {
+        if let AppState::Chat { search, .. } = &mut self.state {
+            *search = value;
+        }
+    }
+
+    /// Scrolls so that message `index` sits at the bottom of the chat panel.
+    fn scroll_to_message(&mut self, index: usize) {
+        let width = self.layout.chat.width;
+        let max_scroll = self.layout.chat_lines.saturating_sub(self.layout.chat.height as usize);
+        if let AppState::Chat { messages, scroll, .. } = &mut self.state {
+            let below: usize = messages
+                .iter()
+                .skip(index + 1)
+                .map(|m| message_lines(m, width, None, &self.theme).len())
+                .sum();
+            *scroll = below.min(max_scroll);
+        }
+    }
+
+    fn save_config(&mut self) {
+        if let Err(e) = self.config.save() {
+            self.add_message("System".to_string(), format!("Couldn't save settings: {}", e));
+        }
+    }
+
+    /// Remembers who spoke last, for the spotlight layout.
+    pub fn mark_active(&mut self, id: Uuid) {
+        self.active_speaker = Some(id);
     }
 
     pub fn add_message(&mut self, username: String, text: String) {
+        self.push_message(MessageKind::Chat, username, text);
+    }
+
+    pub fn push_message(&mut self, kind: MessageKind, username: String, text: String) {
+        self.push_entry(kind, None, username, text, false);
+    }
+
+    /// Whether screen effects are still playing and need a redraw every frame.
+    pub fn animating(&self) -> bool {
+        Instant::now() < self.effects_until
+    }
+
+    /// Our current name, following `/nick` changes once the server has told us our id.
+    pub fn own_name(&self) -> Option<&str> {
+        let AppState::Chat { username, users, .. } = &self.state else {
+            return None;
+        };
+        let listed = self
+            .own_id
+            .and_then(|id| users.iter().find(|u| u.id == id))
+            .map(|u| u.username.as_str());
+        Some(listed.unwrap_or(username))
+    }
+
+    /// A chat or `/me` message from someone, checked for mentions of our name.
+    pub fn receive_message(&mut self, kind: MessageKind, id: Uuid, username: String, text: String) {
+        if let Some(identity) = self.ignored_identity(id) {
+            *self.hidden.entry(identity).or_default() += 1;
+            return;
+        }
+        let own = self.own_id == Some(id);
+        let mention = !own && self.own_name().is_some_and(|name| notify::mentions(&text, name));
+        if mention {
+            let timestamp = chrono::Local::now().format("%H:%M:%S").to_string();
+            self.mentions.push_back(format!("[{}] {}: {}", timestamp, username, text));
+            if self.mentions.len() > MAX_MENTIONS {
+                self.mentions.pop_front();
+            }
+            self.unread_mentions += 1;
+            self.notify(NotifyEvent::Mention, format!("{} mentioned you", username), text.clone());
+        }
+        self.push_entry(kind, Some(id), username, text, mention);
+    }
+
+    pub fn receive_direct(&mut self, from: Uuid, from_name: String, to_name: String, text: String) {
+        if let Some(identity) = self.ignored_identity(from) {
+            *self.hidden.entry(identity).or_default() += 1;
+            return;
+        }
+        if self.own_id != Some(from) {
+            self.notify(NotifyEvent::Direct, format!("Message from {}", from_name), text.clone());
+        }
+        self.push_entry(MessageKind::Private, Some(from), format!("{} → {}", from_name, to_name), text, false);
+    }
+
+    /// Shows a message we just sent as `message_id` right away, dimmed until the server echoes it.
+    pub fn message_sent(&mut self, message_id: Uuid, text: String) {
+        let username = self.own_name().unwrap_or_default().to_string();
+        self.push_entry(MessageKind::Chat, self.own_id, username, text, false);
         if let AppState::Chat { messages, .. } = &mut self.state {
+            if let Some(message) = messages.back_mut() {
+                message.delivery = Some(Delivery::Pending { id: message_id, since: Instant::now() });
+            }
+        }
+    }
+
+    /// Marks our message `message_id` delivered; false if it isn't one we're showing.
+    pub fn message_echoed(&mut self, message_id: Uuid) -> bool {
+        // Made it before the connection dropped after all; no need to send it again
+        self.outbox.retain(|outgoing| outgoing.message_id != message_id);
+        let AppState::Chat { messages, .. } = &mut self.state else {
+            return false;
+        };
+        let sent = messages.iter_mut().rev().find(|m| {
+            matches!(m.delivery, Some(Delivery::Pending { id, .. } | Delivery::Failed { id }) if id == message_id)
+        });
+        let Some(message) = sent else {
+            return false;
+        };
+        message.delivery = Some(Delivery::Delivered);
+        true
+    }
+
+    /// Gives up on messages the server hasn't echoed within `DELIVERY_TIMEOUT`.
+    pub fn expire_pending(&mut self) {
+        let AppState::Chat { messages, .. } = &mut self.state else {
+            return;
+        };
+        for message in messages.iter_mut() {
+            match message.delivery {
+                Some(Delivery::Pending { id, since }) if since.elapsed() >= DELIVERY_TIMEOUT => {
+                    message.delivery = Some(Delivery::Failed { id });
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub fn user_joined(&mut self, id: Uuid, username: String, identity: Option<Uuid>) {
+        // They aren't in the user list yet, so go by the identity they joined with
+        if identity.is_some_and(|identity| self.config.ignored.iter().any(|i| i.identity == identity)) {
+            return;
+        }
+        if self.own_id != Some(id) {
+            self.notify(NotifyEvent::Join, format!("{} joined", username), String::new());
+        }
+        self.add_message("System".to_string(), format!("{} joined", username));
+    }
+
+    /// The server accepted our join, or our rejoin after a reconnect, as `id`.
+    pub fn session_started(&mut self, id: Uuid, resumed: bool) {
+        let text = match (self.own_id, resumed) {
+            (None, _) => format!("{} joined", self.own_name().unwrap_or_default()),
+            (Some(_), true) => "Reconnected".to_string(),
+            (Some(_), false) => "Reconnected as a new session; messages sent meanwhile were missed".to_string(),
+        };
+        // Anything not yet echoed may have been lost with the old connection; send it
+        // again under the same id, which the server drops if it did get through
+        if self.own_id.is_some() {
+            let unconfirmed = self.take_messages(|m| {
+                matches!(m.delivery, Some(Delivery::Pending { .. } | Delivery::Failed { .. }))
+            });
+            for message in unconfirmed.into_iter().rev() {
+                if let Some(Delivery::Pending { id, .. } | Delivery::Failed { id }) = message.delivery {
+                    self.outbox.push_front(Outgoing { message_id: id, text: message.text });
+                }
+            }
+        }
+        self.own_id = Some(id);
+        self.add_message("System".to_string(), text);
+    }
+
+    /// Removes the messages `remove` picks, keeping the search on the message it was showing.
+    fn take_messages(&mut self, remove: impl Fn(&ChatMessage) -> bool) -> Vec<ChatMessage> {
+        let AppState::Chat { messages, search, .. } = &mut self.state else {
+            return Vec::new();
+        };
+        if let Some(search) = search {
+            search.current = search
+                .current
+                .filter(|&i| !remove(&messages[i]))
+                .map(|i| i - messages.range(..i).filter(|m| remove(m)).count());
+        }
+        let (removed, kept) = std::mem::take(messages).into_iter().partition(|m| remove(m));
+        *messages = kept;
+        removed.into()
+    }
+
+    fn notify(&mut self, event: NotifyEvent, title: String, body: String) {
+        if !self.config.notifications.methods(event).is_empty() {
+            self.notifications.push(Notification { event, title, body });
+        }
+    }
+
+    fn push_entry(&mut self, kind: MessageKind, sender: Option<Uuid>, username: String, text: String, mention: bool) {
+        let width = self.layout.chat.width;
+        let history_size = self.config.history_size.max(1);
+        if let AppState::Chat { messages, scroll, unseen, search, .. } = &mut self.state {
             let timestamp = chrono::Local::now().format("%H:%M:%S").to_string();
-            messages.push_back(ChatMessage {
+            let message = ChatMessage {
+                kind,
+                sender,
+                mention,
                 username,
                 text,
                 timestamp,
-            });
+                delivery: None,
+            };
+            
+            if self.layout.compact && self.tab != Tab::Chat {
+                self.tab_unread += 1;
+            }
+            // Keep the scrolled-up view where it is
+            if *scroll > 0 {
+                *scroll += message_lines(&message, width, None, &self.theme).len();
+                *unseen += 1;
+            }
+            messages.push_back(message);
             
-            // Keep only last 100 messages
-            while messages.len() > 100 {
+            while messages.len() > history_size {
                 messages.pop_front();
+                // The match being shown may be the message that just went
+                if let Some(search) = search {
+                    search.current = search.current.and_then(|index| index.checked_sub(1));
+                }
             }
         }
     }
@@ -143,21 +1161,247 @@ impl App {
         }
     }
 
+    pub fn users(&self) -> &[UserInfo] {
+        match &self.state {
+            AppState::Chat { users, .. } => users,
+            _ => &[],
+        }
+    }
+
     pub fn update_video_frame(&mut self, frame: AsciiFrame) {
         if let AppState::Chat { video_frame, .. } = &mut self.state {
             *video_frame = Some(frame);
         }
     }
 
-    pub fn update_remote_frame(&mut self, username: String, frame: AsciiFrame) {
+    /// Space available to draw a remote participant's video; zero when they aren't on screen.
+    pub fn remote_tile_size(&self, id: Uuid) -> (u16, u16) {
+        self.layout.tile_size(Some(id))
+    }
+
+    pub fn local_tile_size(&self) -> (u16, u16) {
+        self.layout.tile_size(None)
+    }
+
+    /// Largest tile on screen, reported to senders as our preferred receive size.
+    pub fn largest_tile_size(&self) -> (u16, u16) {
+        self.layout
+            .tiles
+            .iter()
+            .map(|(_, area)| inner_size(*area))
+            .max_by_key(|(w, h)| *w as u32 * *h as u32)
+            .unwrap_or((0, 0))
+    }
+
+    /// Our long-lived identity, created and saved the first time it's needed.
+    pub fn identity(&mut self) -> Uuid {
+        if let Some(identity) = self.config.identity {
+            return identity;
+        }
+        let identity = Uuid::new_v4();
+        self.config.identity = Some(identity);
+        self.save_config();
+        identity
+    }
+
+    /// Their identity, if `id` is someone we're ignoring.
+    pub fn ignored_identity(&self, id: Uuid) -> Option<Uuid> {
+        let identity = self.users().iter().find(|u| u.id == id)?.identity?;
+        self.config.ignored.iter().any(|i| i.identity == identity).then_some(identity)
+    }
+
+    fn find_user(&self, name: &str) -> Option<Uuid> {
+        let name = name.trim_start_matches('@');
+        self.users()
+            .iter()
+            .find(|u| u.username.eq_ignore_ascii_case(name))
+            .map(|u| u.id)
+    }
+
+    /// Hides or shows everything from `id`, remembering the choice by their identity.
+    fn set_ignored(&mut self, id: Uuid, ignore: bool) {
+        let Some(user) = self.users().iter().find(|u| u.id == id).cloned() else {
+            return;
+        };
+        if self.own_id == Some(id) {
+            self.add_message("Error".to_string(), "You can't ignore yourself".to_string());
+            return;
+        }
+        let Some(identity) = user.identity else {
+            self.add_message("Error".to_string(), format!("{} is on an older client that can't be ignored", user.username));
+            return;
+        };
+        
+        let name = user.username;
+        if ignore {
+            if self.config.ignored.iter().any(|i| i.identity == identity) {
+                self.add_message("System".to_string(), format!("Already ignoring {}", name));
+                return;
+            }
+            self.config.ignored.push(IgnoredUser { identity, username: name.clone() });
+            self.jitter.remove(&id);
+            if self.active_speaker == Some(id) {
+                self.active_speaker = None;
+            }
+            if let AppState::Chat { remote_frames, .. } = &mut self.state {
+                remote_frames.retain(|r| r.id != id);
+            }
+            // What they already said goes too
+            let removed = self.take_messages(|m| m.sender == Some(id)).len();
+            *self.hidden.entry(identity).or_default() += removed;
+            self.add_message(
+                "System".to_string(),
+                format!("Ignoring {}: their messages and video are hidden (/unignore {} to undo)", name, name),
+            );
+        } else {
+            if !self.config.ignored.iter().any(|i| i.identity == identity) {
+                self.add_message("System".to_string(), format!("You aren't ignoring {}", name));
+                return;
+            }
+            self.config.ignored.retain(|i| i.identity != identity);
+            self.hidden.remove(&identity);
+            self.add_message("System".to_string(), format!("No longer ignoring {}", name));
+        }
+        self.save_config();
+    }
+
+    /// `/unignore`: someone in the room, or anyone on the list by the name they had when ignored.
+    fn unignore_by_name(&mut self, name: &str) {
+        if let Some(id) = self.find_user(name) {
+            self.set_ignored(id, false);
+            return;
+        }
+        let name = name.trim_start_matches('@');
+        let Some(index) = self.config.ignored.iter().position(|i| i.username.eq_ignore_ascii_case(name)) else {
+            self.add_message("Error".to_string(), format!("You aren't ignoring anyone named {}", name));
+            return;
+        };
+        let removed = self.config.ignored.remove(index);
+        self.hidden.remove(&removed.identity);
+        self.add_message("System".to_string(), format!("No longer ignoring {}", removed.username));
+        self.save_config();
+    }
+
+    fn list_ignored(&mut self) {
+        if self.config.ignored.is_empty() {
+            self.add_message("System".to_string(), "You aren't ignoring anyone".to_string());
+            return;
+        }
+        let lines: Vec<String> = self
+            .config
+            .ignored
+            .iter()
+            .map(|ignored| {
+                let present = self.users().iter().any(|u| u.identity == Some(ignored.identity));
+                let hidden = self.hidden.get(&ignored.identity).copied().unwrap_or(0);
+                format!(
+                    "{}{} · {} hidden",
+                    ignored.username,
+                    if present { " (here)" } else { "" },
+                    hidden,
+                )
+            })
+            .collect();
+        self.add_message("System".to_string(), format!("Ignoring:\n{}", lines.join("\n")));
+    }
+
+    /// Drops their tile and listing once `id` has left.
+    pub fn user_left(&mut self, id: Uuid) {
+        let ignored = self.ignored_identity(id).is_some();
+        self.jitter.remove(&id);
+        self.keepalive.remove(&id);
+        if self.active_speaker == Some(id) {
+            self.active_speaker = None;
+        }
+        if self.selected_user == Some(id) {
+            self.selected_user = None;
+        }
+        let AppState::Chat { users, remote_frames, .. } = &mut self.state else {
+            return;
+        };
+        let name = users
+            .iter()
+            .find(|u| u.id == id)
+            .map(|u| u.username.clone())
+            .or_else(|| remote_frames.iter().find(|r| r.id == id).map(|r| r.username.clone()));
+        users.retain(|u| u.id != id);
+        remote_frames.retain(|r| r.id != id);
+        if ignored {
+            return;
+        }
+        
+        let text = match name {
+            Some(name) => format!("{} left", name),
+            None => "A user left".to_string(),
+        };
+        self.add_message("System".to_string(), text);
+    }
+
+    /// Live while frames keep coming; once they stop, whether the sender is still around.
+    pub fn stream_state(&self, remote: &RemoteVideo) -> StreamState {
+        if remote.updated.elapsed() < self.keepalive(remote.id) + STALE_VIDEO {
+            StreamState::Live
+        } else if self.connection != ConnectionStatus::Connected || !self.users().iter().any(|u| u.id == remote.id) {
+            StreamState::Disconnected
+        } else {
+            StreamState::Paused
+        }
+    }
+
+    /// Longest `id` may go without sending a frame while its picture is static.
+    fn keepalive(&self, id: Uuid) -> Duration {
+        self.keepalive.get(&id).copied().unwrap_or(DEFAULT_KEEPALIVE)
+    }
+
+    /// Holds a remote frame in its sender's jitter buffer; unstamped frames are shown right away.
+    pub fn queue_remote_frame(&mut self, id: Uuid, username: String, timestamp_ms: u64, keepalive_ms: u64, frame: AsciiFrame) {
+        if self.ignored_identity(id).is_some() {
+            return;
+        }
+        if keepalive_ms > 0 {
+            self.keepalive.insert(id, Duration::from_millis(keepalive_ms));
+        }
+        if timestamp_ms == 0 {
+            self.update_remote_frame(id, username, frame);
+            return;
+        }
+        let now = Instant::now();
+        self.jitter
+            .entry(id)
+            .or_insert_with(|| JitterBuffer::new(now))
+            .push(timestamp_ms, username, frame, now);
+    }
+
+    /// Shows each remote frame whose playout time has come; true if any were.
+    pub fn play_remote_frames(&mut self) -> bool {
+        let now = Instant::now();
+        let due: Vec<(Uuid, String, AsciiFrame)> = self
+            .jitter
+            .iter_mut()
+            .filter_map(|(&id, buffer)| buffer.pop_due(now).map(|(username, frame)| (id, username, frame)))
+            .collect();
+        let played = !due.is_empty();
+        for (id, username, frame) in due {
+            self.update_remote_frame(id, username, frame);
+        }
+        played
+    }
+
+    /// When the next held remote frame is due.
+    pub fn next_playout(&self) -> Option<Instant> {
+        self.jitter.values().filter_map(JitterBuffer::next_due).min()
+    }
+
+    pub fn update_remote_frame(&mut self, id: Uuid, username: String, frame: AsciiFrame) {
         if let AppState::Chat { remote_frames, .. } = &mut self.state {
-            // Keep only latest frame per user
-            remote_frames.retain(|(u, _)| u != &username);
-            remote_frames.push((username, frame));
-            
-            // Limit to 4 remote videos
-            if remote_frames.len() > 4 {
-                remote_frames.remove(0);
+            // Keep only latest frame per user, in order of first appearance
+            match remote_frames.iter_mut().find(|r| r.id == id) {
+                Some(remote) => {
+                    remote.username = username;
+                    remote.frame = frame;
+                    remote.updated = Instant::now();
+                }
+                None => remote_frames.push(RemoteVideo { id, username, frame, updated: Instant::now() }),
             }
         }
     }
@@ -166,41 +1410,53 @@ impl App {
 pub enum UserAction {
     JoinChat(String),
     SendMessage(String),
+    Command { name: String, args: String },
+    SetCaption(Option<String>),
+    CopyToClipboard(String),
 }
 
 pub fn draw(f: &mut Frame, app: &mut App, elapsed: std::time::Duration) {
     let area = f.area();
     
     // Main border
-    let block = Block::default()
+    let mut block = Block::default()
         .title(" Terminal Chat // ASCII Vision ")
         .borders(Borders::ALL)
         .border_type(ratatui::widgets::BorderType::Thick)
-        .style(Style::default().fg(Color::Cyan));
+        .style(Style::default().fg(app.theme.frame).bg(app.theme.background));
+    if matches!(app.state, AppState::Chat { .. }) {
+        block = block.title_bottom(status_line(app).right_aligned());
+    }
     
     let inner = block.inner(area);
+    if area.width < MIN_WIDTH || area.height < MIN_HEIGHT {
+        app.layout = ChatLayout::default();
+        draw_too_small(f, area, &app.theme);
+        return;
+    }
     f.render_widget(block, area);
     
     match &app.state {
         AppState::UsernameEntry(buffer) => {
-            draw_username_entry(f, inner, buffer, &app.ngrok_url);
+            draw_username_entry(f, inner, buffer, app);
         }
-        AppState::Chat {
-            input_buffer,
-            messages,
-            users,
-            video_frame,
-            ..
-        } => {
-            draw_chat(f, inner, input_buffer, messages, users, video_frame.as_ref());
+        AppState::Chat { .. } => {
+            app.layout = draw_chat(f, inner, app);
         }
     }
     
+    match app.dialog {
+        Some(Dialog::Help { scroll }) => draw_help(f, inner, app, scroll),
+        Some(Dialog::ConfirmQuit) => draw_confirm_quit(f, inner, &app.theme),
+        None => {}
+    }
+    
     // Apply effects
     app.effects.process_effects(elapsed.into(), f.buffer_mut(), area);
 }
 
-fn draw_username_entry(f: &mut Frame, area: Rect, buffer: &str, ngrok_url: &Option<String>) {
+fn draw_username_entry(f: &mut Frame, area: Rect, buffer: &str, app: &App) {
+    let theme = &app.theme;
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .margin(2)
@@ -213,22 +1469,26 @@ fn draw_username_entry(f: &mut Frame, area: Rect, buffer: &str, ngrok_url: &Opti
         .split(area);
     
     let title = Paragraph::new("Welcome to Terminal Chat")
-        .style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))
+        .style(Style::default().fg(theme.title).add_modifier(Modifier::BOLD))
         .alignment(Alignment::Center);
     
     let input = Paragraph::new(format!("Username: {}_", buffer))
-        .style(Style::default().fg(Color::Green))
+        .style(Style::default().fg(theme.accent))
         .alignment(Alignment::Center)
         .block(Block::default().borders(Borders::ALL));
     
     let mut help_text = vec!["Enter your username to join the chat room".to_string()];
-    if let Some(url) = ngrok_url {
+    if let Some(url) = &app.ngrok_url {
         help_text.push(format!("Share this URL with others: {}", url));
     }
     help_text.push("Press ESC to quit".to_string());
+    // The full error is in the chat once joined
+    if let Some(error) = app.config.load_error.as_deref().and_then(|e| e.lines().next()) {
+        help_text.push(format!("Using default settings: {}", error));
+    }
     
     let help = Paragraph::new(help_text.join("\n"))
-        .style(Style::default().fg(Color::Gray))
+        .style(Style::default().fg(theme.dim))
         .alignment(Alignment::Center)
         .wrap(Wrap { trim: true });
     
@@ -237,103 +1497,846 @@ fn draw_username_entry(f: &mut Frame, area: Rect, buffer: &str, ngrok_url: &Opti
     f.render_widget(help, chunks[2]);
 }
 
-fn draw_chat(
-    f: &mut Frame,
-    area: Rect,
-    input: &str,
-    messages: &VecDeque<ChatMessage>,
-    users: &[UserInfo],
-    video_frame: Option<&AsciiFrame>,
-) {
-    // Layout: [Video | Chat | Users]
-    let main_chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([
-            Constraint::Percentage(30), // Video
-            Constraint::Percentage(50), // Chat
-            Constraint::Percentage(20), // Users
-        ])
-        .split(area);
+/// Connection state for the bottom border, with our round trip time while connected.
+fn status_line(app: &App) -> Line<'static> {
+    let (text, color) = match app.connection {
+        ConnectionStatus::Connected => {
+            let rtt = app
+                .own_id
+                .and_then(|id| app.users().iter().find(|u| u.id == id))
+                .and_then(|u| u.rtt_ms)
+                .map(|ms| format!(" · {} ms", ms))
+                .unwrap_or_default();
+            (format!(" ● connected{} ", rtt), app.theme.accent)
+        }
+        ConnectionStatus::Reconnecting { attempt, retry_at } => {
+            let wait = retry_at.saturating_duration_since(Instant::now()).as_secs_f32().ceil() as u64;
+            let text = if wait > 0 {
+                format!(" ◌ reconnecting in {}s (attempt {}) ", wait, attempt)
+            } else {
+                format!(" ◌ reconnecting (attempt {}) ", attempt)
+            };
+            (text, app.theme.title)
+        }
+        ConnectionStatus::Offline => (" ○ offline ".to_string(), app.theme.mention),
+    };
+    Line::styled(text, Style::default().fg(color))
+}
+
+fn draw_too_small(f: &mut Frame, area: Rect, theme: &Theme) {
+    let text = format!(
+        "Terminal too small\n{}x{}, need {}x{}",
+        area.width, area.height, MIN_WIDTH, MIN_HEIGHT
+    );
+    let y = area.y + area.height.saturating_sub(2) / 2;
+    let message = Paragraph::new(text)
+        .alignment(Alignment::Center)
+        .style(Style::default().fg(theme.title).bg(theme.background))
+        .wrap(Wrap { trim: true });
+    f.render_widget(Block::default().style(Style::default().bg(theme.background)), area);
+    f.render_widget(message, Rect { y, height: area.height.min(2), ..area });
+}
+
+fn draw_chat(f: &mut Frame, area: Rect, app: &App) -> ChatLayout {
+    let mut layout = ChatLayout::default();
+    let video_pct = app.config.video_percent.clamp(10, 70);
+    let chat_pct = app.config.chat_percent.clamp(20, 90 - video_pct);
     
-    // Video panel
-    let video_block = Block::default()
-        .title(" You ")
-        .borders(Borders::ALL)
-        .style(Style::default().fg(Color::Magenta));
+    if area.width < app.config.compact_width || area.height < app.config.compact_height {
+        draw_tabbed(f, area, app, &mut layout);
+        return layout;
+    }
     
-    let video_area = video_block.inner(main_chunks[0]);
-    f.render_widget(video_block, main_chunks[0]);
+    match app.config.layout {
+        LayoutMode::Gallery | LayoutMode::Spotlight => {
+            // Layout: [Video | Chat | Users]
+            let main_chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([
+                    Constraint::Percentage(video_pct),
+                    Constraint::Percentage(chat_pct),
+                    Constraint::Percentage(100 - video_pct - chat_pct),
+                ])
+                .split(area);
+            layout.video = main_chunks[0];
+            layout.columns = area;
+            layout.borders = Some((main_chunks[1].x, main_chunks[2].x));
+            
+            if app.config.layout == LayoutMode::Gallery {
+                draw_gallery(f, main_chunks[0], app, &mut layout);
+            } else {
+                draw_spotlight(f, main_chunks[0], app, &mut layout);
+            }
+            draw_messages(f, main_chunks[1], app, &mut layout);
+            draw_users(f, main_chunks[2], app, &mut layout);
+        }
+        LayoutMode::ChatFocus => {
+            // Layout: [Thumbnails | Chat | Users]
+            let main_chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([
+                    Constraint::Length(THUMBNAIL_WIDTH),
+                    Constraint::Min(20),
+                    Constraint::Percentage(100 - video_pct - chat_pct),
+                ])
+                .split(area);
+            
+            layout.video = main_chunks[0];
+            let block = video_panel_block(" Video ", &app.theme);
+            let inner = block.inner(main_chunks[0]);
+            f.render_widget(block, main_chunks[0]);
+            draw_filmstrip(f, inner, app, &all_tiles(app), Direction::Vertical, &mut layout);
+            
+            draw_messages(f, main_chunks[1], app, &mut layout);
+            draw_users(f, main_chunks[2], app, &mut layout);
+        }
+        LayoutMode::VideoOnly => {
+            layout.video = area;
+            draw_gallery(f, area, app, &mut layout);
+        }
+    }
     
-    if let Some(frame) = video_frame {
-        render_ascii_frame(f, video_area, frame);
-    } else {
-        let loading = Paragraph::new("Camera loading...")
-            .alignment(Alignment::Center)
-            .style(Style::default().fg(Color::DarkGray));
-        f.render_widget(loading, video_area);
+    layout
+}
+
+/// One panel at a time under a tab bar, for terminals too small for the side-by-side columns.
+fn draw_tabbed(f: &mut Frame, area: Rect, app: &App, layout: &mut ChatLayout) {
+    layout.compact = true;
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(1)])
+        .split(area);
+    
+    // Laid out by hand, rather than with the Tabs widget, so clicks can be mapped back
+    let mut spans = Vec::new();
+    let mut x = chunks[0].x;
+    for tab in Tab::ALL {
+        let title = match tab {
+            Tab::Chat if app.tab_unread > 0 => format!(" Chat ({} new) ", app.tab_unread),
+            Tab::Chat => " Chat ".to_string(),
+            Tab::Video => format!(" Video ({}) ", all_tiles(app).len()),
+            Tab::Users => format!(" Users ({}) ", app.users().len()),
+        };
+        let style = if tab == app.tab {
+            Style::default().fg(app.theme.highlight_fg).bg(app.theme.highlight_bg).add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(app.theme.dim)
+        };
+        let width = title.chars().count() as u16;
+        layout.tabs.push((tab, Rect { x, width, ..chunks[0] }.intersection(chunks[0])));
+        x += width + 1;
+        spans.push(Span::styled(title, style));
+        spans.push(Span::raw(" "));
+    }
+    let hint = Span::styled(key_hint(app, &[Action::NextTab]), Style::default().fg(app.theme.dim));
+    f.render_widget(Paragraph::new(Line::from(spans)), chunks[0]);
+    f.render_widget(Paragraph::new(hint).alignment(Alignment::Right), chunks[0]);
+    
+    match app.tab {
+        Tab::Chat => draw_messages(f, chunks[1], app, layout),
+        Tab::Video => {
+            layout.video = chunks[1];
+            if app.config.layout == LayoutMode::Spotlight {
+                draw_spotlight(f, chunks[1], app, layout);
+            } else {
+                draw_gallery(f, chunks[1], app, layout);
+            }
+        }
+        Tab::Users => draw_users(f, chunks[1], app, layout),
     }
+}
+
+fn draw_messages(f: &mut Frame, area: Rect, app: &App, layout: &mut ChatLayout) {
+    let AppState::Chat { input, messages, scroll, unseen, search, completion, .. } = &app.state else {
+        return;
+    };
     
-    // Chat panel
+    // Chat panel; the input box grows with multi-line messages
+    let input_lines = input.line_count().min(MAX_INPUT_LINES) as u16;
     let chat_chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Min(1), Constraint::Length(3)])
-        .split(main_chunks[1]);
+        .constraints([Constraint::Min(1), Constraint::Length(input_lines + 2)])
+        .split(area);
     
-    let messages_block = Block::default()
-        .title(" Chat ")
+    let mut title = match &app.topic {
+        Some(topic) => format!(" Chat — {} ", topic),
+        None => " Chat ".to_string(),
+    };
+    if app.unread_mentions > 0 {
+        title.push_str(&format!("@{} [/mentions] ", app.unread_mentions));
+    }
+    let hidden: usize = app.hidden.values().sum();
+    if hidden > 0 {
+        title.push_str(&format!("{} hidden [/ignore] ", hidden));
+    }
+    let mut messages_block = Block::default()
+        .title(title)
+        .title_bottom(format!(" {}{} ", app.config.layout.label(), key_hint(app, &[Action::CycleLayout])))
         .borders(Borders::ALL)
-        .style(Style::default().fg(Color::Green));
+        .style(Style::default().fg(app.theme.chat_border));
+    if *scroll > 0 {
+        let key = key_hint(app, &[Action::ScrollBottom]);
+        let hint = if *unseen > 0 {
+            format!(" ↓ {} new below{} ", unseen, key)
+        } else {
+            format!(" ↓ more below{} ", key)
+        };
+        messages_block = messages_block.title_bottom(Line::from(hint).right_aligned());
+    }
     
     let messages_area = messages_block.inner(chat_chunks[0]);
     f.render_widget(messages_block, chat_chunks[0]);
     
-    // Render messages
-    let msg_items: Vec<ListItem> = messages
+    // Render messages, anchored to the bottom of the panel
+    let query = search.as_ref().map(|s| s.query.to_lowercase()).filter(|q| !q.is_empty());
+    let mut lines: Vec<(usize, Line)> = messages
         .iter()
-        .map(|m| {
-            let content = format!("[{}] {}: {}", m.timestamp, m.username, m.text);
-            ListItem::new(content)
+        .enumerate()
+        .flat_map(|(i, m)| {
+            message_lines(m, messages_area.width, query.as_deref(), &app.theme)
+                .into_iter()
+                .map(move |line| (i, line))
         })
         .collect();
     
-    let messages_list = List::new(msg_items)
-        .style(Style::default().fg(Color::White));
+    // Messages waiting for the connection sit below the conversation
+    for Outgoing { text, .. } in &app.outbox {
+        let queued = ChatMessage {
+            kind: MessageKind::Chat,
+            sender: None,
+            mention: false,
+            username: app.own_name().unwrap_or_default().to_string(),
+            text: text.clone(),
+            timestamp: "queued".to_string(),
+            delivery: Some(Delivery::Queued),
+        };
+        for line in message_lines(&queued, messages_area.width, None, &app.theme) {
+            lines.push((usize::MAX, line));
+        }
+    }
+    
+    let height = messages_area.height as usize;
+    let scroll = (*scroll).min(lines.len().saturating_sub(height));
+    let end = lines.len() - scroll;
+    let start = end.saturating_sub(height);
+    let shown = end - start;
     
-    f.render_widget(messages_list, messages_area);
+    let text_area = Rect {
+        y: messages_area.y + (height - shown) as u16,
+        height: shown as u16,
+        ..messages_area
+    };
+    let visible = &lines[start..end];
+    let messages_text = Paragraph::new(visible.iter().map(|(_, line)| line.clone()).collect::<Vec<_>>())
+        .style(Style::default().fg(app.theme.text));
+    f.render_widget(messages_text, text_area);
+    
+    layout.chat = messages_area;
+    layout.chat_lines = lines.len();
+    layout.chat_rows = visible
+        .iter()
+        .map(|(i, line)| (*i, line.spans.iter().map(|s| s.content.as_ref()).collect()))
+        .collect();
+    layout.chat_top = text_area.y;
     
     // Input box
-    let input_block = Block::default()
+    let mut input_block = Block::default()
         .borders(Borders::ALL)
-        .style(Style::default().fg(Color::Yellow));
-    
-    let input_text = Paragraph::new(format!("> {}_", input))
-        .style(Style::default().fg(Color::White));
+        .style(Style::default().fg(app.theme.input_border));
+    if app.keymap.preset == Preset::Vi {
+        input_block = input_block.title(if app.vi_normal { " NORMAL " } else { " INSERT " });
+    }
     
+    let input_area = input_block.inner(chat_chunks[1]);
     f.render_widget(input_block, chat_chunks[1]);
-    f.render_widget(input_text, chat_chunks[1].inner(Margin::new(1, 0)));
+    
+    if let Some(active) = completion.as_ref().filter(|c| c.candidates.len() > 1) {
+        draw_completion_popup(f, messages_area, active, &app.theme);
+    }
+    
+    match search {
+        Some(search) => {
+            let count = query.as_deref().map_or(0, |q| messages.iter().filter(|m| message_matches(m, q)).count());
+            let search_text = Paragraph::new(format!("search: {}_  ({} matches, ↑/↓ Enter Esc)", search.query, count))
+                .style(Style::default().fg(app.theme.input_border));
+            f.render_widget(search_text, input_area);
+        }
+        None => {
+            // Prompt on the first line, continuation lines indented to match
+            let text_width = input_area.width.saturating_sub(2) as usize;
+            let (lines, (cursor_x, cursor_y), first_row) = input.view(text_width, MAX_INPUT_LINES);
+            let lines: Vec<Line> = lines
+                .into_iter()
+                .enumerate()
+                .map(|(i, line)| Line::from(format!("{}{}", if first_row + i == 0 { "> " } else { "  " }, line)))
+                .collect();
+            let input_text = Paragraph::new(lines)
+                .style(Style::default().fg(app.theme.text));
+            f.render_widget(input_text, input_area);
+            f.set_cursor_position((input_area.x + 2 + cursor_x, input_area.y + cursor_y));
+        }
+    }
+}
+
+/// Current key bindings, generated from the keymap so overrides show up.
+fn draw_help(f: &mut Frame, area: Rect, app: &App, scroll: u16) {
+    let key_style = Style::default().fg(app.theme.title);
+    let heading = Style::default().fg(app.theme.accent).add_modifier(Modifier::BOLD);
+    let rows = |bindings: Vec<(String, &'static str)>| -> Vec<Line<'static>> {
+        let width = bindings.iter().map(|(keys, _)| keys.chars().count()).max().unwrap_or(0) + 2;
+        bindings
+            .into_iter()
+            .map(|(keys, description)| {
+                Line::from(vec![Span::styled(format!("{:<width$}", keys), key_style), Span::raw(description)])
+            })
+            .collect()
+    };
+    
+    let mut lines = Vec::new();
+    if app.keymap.preset == Preset::Vi {
+        lines.push(Line::styled("Normal mode", heading));
+        lines.extend(rows(app.keymap.describe(true)));
+        lines.push(Line::default());
+        lines.push(Line::styled("Insert mode", heading));
+    }
+    lines.extend(rows(app.keymap.describe(false)));
+    lines.push(Line::default());
+    lines.push(Line::styled("Search", heading));
+    lines.extend(rows(vec![
+        ("Up, Down".to_string(), "Older / newer match"),
+        ("Enter".to_string(), "Stay at this match"),
+        ("Esc".to_string(), "Close and jump to newest"),
+    ]));
+    lines.push(Line::default());
+    lines.push(Line::styled("Mouse", heading));
+    lines.extend(rows(vec![
+        ("Wheel".to_string(), "Scroll chat or gallery pages"),
+        ("Click".to_string(), "Select user, enlarge tile, copy link"),
+        ("Drag".to_string(), "Resize panels"),
+    ]));
+    lines.push(Line::default());
+    lines.push(Line::styled("Type /help for commands. Bindings: [keys] in config.toml", Style::default().fg(app.theme.dim)));
+    
+    let popup = centered(area, 72, lines.len() as u16 + 2);
+    let max_scroll = (lines.len() as u16).saturating_sub(popup.height.saturating_sub(2));
+    let help = Paragraph::new(lines)
+        .scroll((scroll.min(max_scroll), 0))
+        .style(Style::default().fg(app.theme.text).bg(app.theme.background))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Keys (Esc to close) ")
+                .style(Style::default().fg(app.theme.frame)),
+        );
+    f.render_widget(Clear, popup);
+    f.render_widget(help, popup);
+}
+
+fn draw_confirm_quit(f: &mut Frame, area: Rect, theme: &Theme) {
+    let popup = centered(area, 30, 3);
+    let text = Paragraph::new("Leave the chat? (y/n)")
+        .alignment(Alignment::Center)
+        .style(Style::default().fg(theme.text).bg(theme.background))
+        .block(Block::default().borders(Borders::ALL).style(Style::default().fg(theme.title)));
+    f.render_widget(Clear, popup);
+    f.render_widget(text, popup);
+}
+
+/// A `width` x `height` rect in the middle of `area`, shrunk to fit.
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
+
+/// Candidate list floating at the bottom-left of the message area.
+fn draw_completion_popup(f: &mut Frame, area: Rect, completion: &Completion, theme: &Theme) {
+    let shown = completion.candidates.len().min(8);
+    // Keep the selected candidate inside the visible window
+    let first = completion.index.saturating_sub(shown - 1);
+    let width = completion
+        .candidates
+        .iter()
+        .map(|c| c.chars().count() as u16)
+        .max()
+        .unwrap_or(0)
+        + 4;
+    let popup = Rect {
+        x: area.x,
+        y: area.y + area.height.saturating_sub(shown as u16 + 2),
+        width: width.min(area.width),
+        height: (shown as u16 + 2).min(area.height),
+    };
+    
+    let items: Vec<ListItem> = completion
+        .candidates
+        .iter()
+        .skip(first)
+        .take(shown)
+        .map(|c| ListItem::new(c.trim_end().to_string()))
+        .collect();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(" Tab "))
+        .style(Style::default().fg(theme.text))
+        .highlight_style(Style::default().fg(theme.highlight_fg).bg(theme.highlight_bg));
+    
+    let mut list_state = ListState::default().with_selected(Some(completion.index - first));
+    f.render_widget(Clear, popup);
+    f.render_stateful_widget(list, popup, &mut list_state);
+}
+
+fn message_matches(message: &ChatMessage, query: &str) -> bool {
+    !query.is_empty() && message.text.to_lowercase().contains(query)
+}
+
+/// A chat message as wrapped lines, with `query` (lowercase) highlighted.
+fn message_lines(message: &ChatMessage, width: u16, query: Option<&str>, theme: &Theme) -> Vec<Line<'static>> {
+    let (before, after) = match message.kind {
+        MessageKind::Chat => ("", ": "),
+        MessageKind::Action => ("* ", " "),
+        MessageKind::Private => ("[", "] "),
+    };
+    let prefix_style = if message.mention {
+        Style::default().fg(theme.mention).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default()
+    };
+    let name_style = match message.sender {
+        Some(id) => prefix_style.fg(theme.user_color(id)),
+        None => prefix_style,
+    };
+    let mut spans = vec![
+        Span::styled(format!("[{}] {}", message.timestamp, before), prefix_style),
+        Span::styled(message.username.clone(), name_style),
+        Span::styled(after, prefix_style),
+    ];
+    let match_style = Style::default().fg(theme.highlight_fg).bg(theme.highlight_bg);
+    spans.extend(highlight(markdown::render(&message.text, theme), query, match_style));
+    
+    match message.delivery {
+        Some(Delivery::Queued | Delivery::Pending { .. }) => {
+            // Dimmed until the server has it
+            let pending = Style::default().fg(theme.dim).add_modifier(Modifier::ITALIC);
+            spans.iter_mut().for_each(|span| span.style = span.style.patch(pending));
+        }
+        Some(Delivery::Failed { .. }) => {
+            spans.push(Span::styled(" ✗ not delivered", Style::default().fg(theme.error)));
+        }
+        Some(Delivery::Delivered) | None => {}
+    }
+    wrap_spans(spans, width as usize)
+}
+
+/// The link in `message` that the word at display column `column` of the wrapped
+/// `row` belongs to: either its start, or a wrapped-over piece at the row start.
+fn url_at(message: &str, row: &str, column: u16) -> Option<String> {
+    let mut x = 0;
+    let hit = row.char_indices().find_map(|(i, ch)| {
+        x += ch.width().unwrap_or(0) as u16;
+        (column < x).then_some(i)
+    })?;
+    let start = row[..hit].rfind(' ').map_or(0, |i| i + 1);
+    let end = row[hit..].find(' ').map_or(row.len(), |i| hit + i);
+    let word = &row[start..end];
+    if word.trim().is_empty() {
+        return None;
+    }
+    
+    message
+        .split_whitespace()
+        .map(|token| token.trim_start_matches(['*', '_', '(']))
+        .filter(|token| token.starts_with("http://") || token.starts_with("https://"))
+        .find(|url| url.starts_with(word) || (start == 0 && url.contains(word)))
+        .map(|url| url.trim_end_matches(['.', ',', ')', '!', '?', '*', '_']).to_string())
+}
+
+/// Splits spans so that matches of `query` (lowercase) get `match_style` on top of their own style.
+fn highlight(spans: Vec<Span<'static>>, query: Option<&str>, match_style: Style) -> Vec<Span<'static>> {
+    let Some(query) = query else {
+        return spans;
+    };
+    spans
+        .into_iter()
+        .flat_map(|span| highlight_span(span, query, match_style))
+        .collect()
+}
+
+/// ` [F5/F6]`-style hint naming the keys bound to `actions`, or nothing if any is unbound.
+fn key_hint(app: &App, actions: &[Action]) -> String {
+    actions
+        .iter()
+        .map(|action| app.keymap.label_for(*action))
+        .collect::<Option<Vec<_>>>()
+        .map_or(String::new(), |labels| format!(" [{}]", labels.join("/")))
+}
+
+fn highlight_span(span: Span<'static>, query: &str, match_style: Style) -> Vec<Span<'static>> {
+    let text = span.content.as_ref();
+    // Lowercasing can change a char's byte length; only highlight when every offset still lines up
+    if text.chars().any(|c| c.to_lowercase().map(char::len_utf8).sum::<usize>() != c.len_utf8()) {
+        return vec![span];
+    }
+    let lower = text.to_lowercase();
+    
+    let mut spans = Vec::new();
+    let mut pos = 0;
+    for (start, _) in lower.match_indices(query) {
+        if start < pos {
+            continue;
+        }
+        spans.push(Span::styled(text[pos..start].to_string(), span.style));
+        spans.push(Span::styled(text[start..start + query.len()].to_string(), span.style.patch(match_style)));
+        pos = start + query.len();
+    }
+    spans.push(Span::styled(text[pos..].to_string(), span.style));
+    spans
+}
+
+/// Greedy word wrap that keeps span styles; words longer than a line are split.
+fn wrap_spans(spans: Vec<Span<'static>>, width: usize) -> Vec<Line<'static>> {
+    let width = width.max(1);
+    let mut rows: Vec<Vec<(char, Style)>> = vec![Vec::new()];
+    let mut row_width = 0;
+    
+    for span in &spans {
+        for ch in span.content.chars() {
+            if ch == '\n' {
+                rows.push(Vec::new());
+                row_width = 0;
+                continue;
+            }
+            let w = ch.width().unwrap_or(0);
+            if row_width + w > width {
+                // Break after the last space on the row if there is one
+                let row = rows.last_mut().unwrap();
+                let carried = match row.iter().rposition(|(c, _)| *c == ' ') {
+                    Some(space) if space + 1 < row.len() => row.split_off(space + 1),
+                    _ => Vec::new(),
+                };
+                row_width = carried.iter().map(|(c, _)| c.width().unwrap_or(0)).sum();
+                rows.push(carried);
+            }
+            rows.last_mut().unwrap().push((ch, span.style));
+            row_width += w;
+        }
+    }
+    
+    rows.into_iter()
+        .map(|row| {
+            let mut line_spans: Vec<Span<'static>> = Vec::new();
+            for (ch, style) in row {
+                match line_spans.last_mut() {
+                    Some(last) if last.style == style => last.content.to_mut().push(ch),
+                    _ => line_spans.push(Span::styled(ch.to_string(), style)),
+                }
+            }
+            Line::from(line_spans)
+        })
+        .collect()
+}
+
+fn draw_users(f: &mut Frame, area: Rect, app: &App, layout: &mut ChatLayout) {
+    let users = app.users();
     
     // Users panel
     let users_block = Block::default()
         .title(format!(" Users ({}) ", users.len()))
         .borders(Borders::ALL)
-        .style(Style::default().fg(Color::Cyan));
+        .style(Style::default().fg(app.theme.users_border));
     
-    let users_area = users_block.inner(main_chunks[2]);
-    f.render_widget(users_block, main_chunks[2]);
+    let users_area = users_block.inner(area);
+    f.render_widget(users_block, area);
     
+    let AppState::Chat { video_frame, remote_frames, .. } = &app.state else {
+        return;
+    };
     let user_items: Vec<ListItem> = users
         .iter()
-        .map(|u| ListItem::new(u.username.clone()))
+        .map(|u| {
+            let own = app.own_id == Some(u.id);
+            let video = if own {
+                video_frame.is_some()
+            } else {
+                remote_frames.iter().any(|r| r.id == u.id && r.updated.elapsed() < app.keepalive(u.id) + VIDEO_TIMEOUT)
+            };
+            let joined = chrono::DateTime::from_timestamp(u.joined_at as i64, 0)
+                .map(|t| t.with_timezone(&chrono::Local).format("%H:%M").to_string())
+                .unwrap_or_default();
+            let rtt = u.rtt_ms.map_or("–".to_string(), |ms| format!("{}ms", ms));
+            // Frames their jitter buffer threw away as late or duplicate
+            let dropped = match app.jitter.get(&u.id) {
+                Some(buffer) if buffer.dropped > 0 => format!(" · {} dropped", buffer.dropped),
+                _ => String::new(),
+            };
+            
+            let ignored = app.ignored_identity(u.id);
+            
+            let name = match ignored {
+                Some(_) => Line::from(vec![
+                    Span::styled(u.username.clone(), Style::default().fg(app.theme.dim).add_modifier(Modifier::CROSSED_OUT)),
+                    Span::styled(" (ignored)", Style::default().fg(app.theme.dim)),
+                ]),
+                None => Line::from(vec![
+                    Span::styled(u.username.clone(), Style::default().fg(app.theme.user_color(u.id))),
+                    Span::raw(if own { " (you)" } else { "" }),
+                ]),
+            };
+            let details = match ignored {
+                Some(identity) => format!("  {} · {} hidden", joined, app.hidden.get(&identity).copied().unwrap_or(0)),
+                None => format!("  {} · {} · {}{}", joined, if video { "cam" } else { "no cam" }, rtt, dropped),
+            };
+            let details = Line::styled(details, Style::default().fg(app.theme.dim));
+            ListItem::new(vec![name, details])
+        })
         .collect();
     
     let users_list = List::new(user_items)
-        .style(Style::default().fg(Color::White))
-        .highlight_style(Style::default().fg(Color::Yellow));
+        .style(Style::default().fg(app.theme.text))
+        .highlight_style(Style::default().fg(app.theme.selected));
+    
+    let mut list_state = ListState::default()
+        .with_selected(app.selected_user.and_then(|id| users.iter().position(|u| u.id == id)));
+    f.render_stateful_widget(users_list, users_area, &mut list_state);
     
-    f.render_widget(users_list, users_area);
+    layout.users = users_area;
+    layout.users_offset = list_state.offset();
+}
+
+fn video_panel_block<'a>(title: &'a str, theme: &Theme) -> Block<'a> {
+    Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .style(Style::default().fg(theme.video_border))
+}
+
+fn all_tiles(app: &App) -> Vec<TileSource> {
+    let remote_count = match &app.state {
+        AppState::Chat { remote_frames, .. } => remote_frames.len(),
+        _ => 0,
+    };
+    std::iter::once(TileSource::Local)
+        .chain((0..remote_count).map(TileSource::Remote))
+        .collect()
 }
 
-fn render_ascii_frame(f: &mut Frame, area: Rect, frame: &AsciiFrame) {
+/// Tiles the local preview and every remote frame across `area`, paging when
+/// tiles would get smaller than the minimum.
+fn draw_gallery(f: &mut Frame, area: Rect, app: &App, layout: &mut ChatLayout) {
+    let inner = area.inner(Margin::new(1, 1));
+    
+    // Local preview is always the first tile
+    let sources = all_tiles(app);
+    let total = sources.len();
+    let per_page = (1..=total)
+        .rev()
+        .find(|&n| {
+            let (w, h) = tile_size(inner, n);
+            w >= MIN_TILE_WIDTH && h >= MIN_TILE_HEIGHT
+        })
+        .unwrap_or(1);
+    let pages = total.div_ceil(per_page);
+    let page = app.gallery_page.min(pages - 1);
+    
+    let title = if pages > 1 {
+        format!(" Video {}/{}{} ", page + 1, pages, key_hint(app, &[Action::PrevPage, Action::NextPage]))
+    } else {
+        format!(" Video ({}) ", total)
+    };
+    f.render_widget(video_panel_block(&title, &app.theme), area);
+    
+    let shown = &sources[page * per_page..total.min((page + 1) * per_page)];
+    let cols = grid_columns(inner, shown.len());
+    let (tile_w, tile_h) = tile_size(inner, shown.len());
+    
+    for (slot, &source) in shown.iter().enumerate() {
+        let tile_area = Rect {
+            x: inner.x + (slot % cols) as u16 * tile_w,
+            y: inner.y + (slot / cols) as u16 * tile_h,
+            width: tile_w,
+            height: tile_h,
+        };
+        layout.tiles.push(draw_tile(f, tile_area, app, source));
+    }
+    
+    layout.gallery_pages = pages;
+}
+
+/// One large tile for the selected (or most recently active) participant,
+/// with everyone else in a filmstrip underneath.
+fn draw_spotlight(f: &mut Frame, area: Rect, app: &App, layout: &mut ChatLayout) {
+    let AppState::Chat { remote_frames, .. } = &app.state else {
+        return;
+    };
+    
+    let focus_id = app
+        .selected_user
+        .filter(|id| remote_frames.iter().any(|r| r.id == *id))
+        .or(app.active_speaker.filter(|id| remote_frames.iter().any(|r| r.id == *id)))
+        .or(remote_frames.first().map(|r| r.id));
+    let focus = match focus_id.and_then(|id| remote_frames.iter().position(|r| r.id == id)) {
+        Some(index) => TileSource::Remote(index),
+        None => TileSource::Local,
+    };
+    let others: Vec<TileSource> = all_tiles(app)
+        .into_iter()
+        .filter(|source| !same_tile(*source, focus))
+        .collect();
+    
+    let panel = video_panel_block(" Spotlight ", &app.theme);
+    let inner = panel.inner(area);
+    f.render_widget(panel, area);
+    
+    if others.is_empty() {
+        layout.tiles.push(draw_tile(f, inner, app, focus));
+        return;
+    }
+    
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(MIN_TILE_HEIGHT), Constraint::Length(FILMSTRIP_HEIGHT)])
+        .split(inner);
+    layout.tiles.push(draw_tile(f, chunks[0], app, focus));
+    draw_filmstrip(f, chunks[1], app, &others, Direction::Horizontal, layout);
+}
+
+/// A single row or column of small tiles; whatever doesn't fit is left out.
+fn draw_filmstrip(
+    f: &mut Frame,
+    area: Rect,
+    app: &App,
+    sources: &[TileSource],
+    direction: Direction,
+    layout: &mut ChatLayout,
+) {
+    let (along, min_len) = match direction {
+        Direction::Horizontal => (area.width, MIN_TILE_WIDTH),
+        Direction::Vertical => (area.height, MIN_TILE_HEIGHT),
+    };
+    let fits = ((along / min_len) as usize).min(sources.len());
+    if fits == 0 {
+        return;
+    }
+    
+    let constraints = vec![Constraint::Ratio(1, fits as u32); fits];
+    let slots = Layout::default()
+        .direction(direction)
+        .constraints(constraints)
+        .split(area);
+    
+    for (slot, &source) in slots.iter().zip(sources) {
+        layout.tiles.push(draw_tile(f, *slot, app, source));
+    }
+}
+
+fn same_tile(a: TileSource, b: TileSource) -> bool {
+    match (a, b) {
+        (TileSource::Local, TileSource::Local) => true,
+        (TileSource::Remote(x), TileSource::Remote(y)) => x == y,
+        _ => false,
+    }
+}
+
+/// Draws one labelled video tile and returns its id and area.
+fn draw_tile(f: &mut Frame, area: Rect, app: &App, source: TileSource) -> (Option<Uuid>, Rect) {
+    let AppState::Chat { video_frame, remote_frames, .. } = &app.state else {
+        return (None, area);
+    };
+    
+    match source {
+        TileSource::Local => {
+            let title = if app.motion { " You ● " } else { " You (still) " };
+            let mut block = Block::default()
+                .title(title)
+                .borders(Borders::ALL)
+                .style(Style::default().fg(app.theme.video_border));
+            if let Some(q) = app.quality {
+                let marker = if q.degraded { "↓ " } else { "" };
+                block = block.title_bottom(format!(" {}{}x{} @{}fps ", marker, q.width, q.height, q.fps));
+            }
+            let frame_area = block.inner(area);
+            f.render_widget(block, area);
+            
+            if let Some(frame) = video_frame {
+                render_ascii_frame(f, frame_area, frame, false);
+            } else {
+                let loading = Paragraph::new("Camera loading...")
+                    .alignment(Alignment::Center)
+                    .style(Style::default().fg(app.theme.dim));
+                f.render_widget(loading, frame_area);
+            }
+            (None, area)
+        }
+        TileSource::Remote(index) => {
+            let remote = &remote_frames[index];
+            let color = if app.selected_user == Some(remote.id) {
+                app.theme.selected
+            } else if app.theme.user_colors {
+                app.theme.user_color(remote.id)
+            } else {
+                app.theme.tile_border
+            };
+            // Frames stopped arriving: keep the last one, dimmed, and say why
+            let label = match app.stream_state(remote) {
+                StreamState::Live => None,
+                StreamState::Paused => Some(" paused "),
+                StreamState::Disconnected => Some(" disconnected "),
+            };
+            let color = if label.is_some() { app.theme.dim } else { color };
+            let block = Block::default()
+                .title(format!(" {} ", remote.username))
+                .borders(Borders::ALL)
+                .style(Style::default().fg(color));
+            let frame_area = block.inner(area);
+            f.render_widget(block, area);
+            render_ascii_frame(f, frame_area, &remote.frame, label.is_some());
+            
+            if let Some(label) = label {
+                let label_area = centered(frame_area, label.len() as u16, 1);
+                f.render_widget(Clear, label_area);
+                f.render_widget(
+                    Paragraph::new(label).style(Style::default().fg(app.theme.text).add_modifier(Modifier::REVERSED)),
+                    label_area,
+                );
+            }
+            (Some(remote.id), area)
+        }
+    }
+}
+
+/// Column count that gives `count` tiles the largest roughly-square tiles
+/// (terminal cells are about twice as tall as they are wide).
+fn grid_columns(area: Rect, count: usize) -> usize {
+    (1..=count.max(1))
+        .max_by_key(|&cols| {
+            let rows = count.max(1).div_ceil(cols);
+            let w = area.width / cols as u16;
+            let h = area.height / rows as u16;
+            (w / 2).min(h)
+        })
+        .unwrap_or(1)
+}
+
+fn tile_size(area: Rect, count: usize) -> (u16, u16) {
+    let cols = grid_columns(area, count);
+    let rows = count.max(1).div_ceil(cols);
+    (area.width / cols as u16, area.height / rows as u16)
+}
+
+fn render_ascii_frame(f: &mut Frame, area: Rect, frame: &AsciiFrame, dim: bool) {
+    // Shrink frames larger than the panel rather than cropping them
+    let scaled;
+    let frame = if frame.width > area.width || frame.height > area.height {
+        scaled = frame.resize(frame.width.min(area.width), frame.height.min(area.height));
+        &scaled
+    } else {
+        frame
+    };
+    
     let content_w = area.width.min(frame.width);
     let content_h = area.height.min(frame.height);
     
@@ -345,7 +2348,10 @@ fn render_ascii_frame(f: &mut Frame, area: Rect, frame: &AsciiFrame) {
         for x in 0..content_w {
             let idx = (y * frame.width + x) as usize;
             if idx < frame.cells.len() {
-                let (ch, r, g, b) = frame.cells[idx];
+                let (ch, mut r, mut g, mut b) = frame.cells[idx];
+                if dim {
+                    (r, g, b) = (r / 2, g / 2, b / 2);
+                }
                 if let Some(cell) = buf.cell_mut((x0 + x, y0 + y)) {
                     cell.set_char(ch);
                     cell.set_fg(Color::Rgb(r, g, b));
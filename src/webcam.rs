@@ -1,6 +1,7 @@
 use anyhow::{Context, Result};
-use crossbeam_channel::{bounded, Receiver, Sender};
+use crossbeam_channel::{bounded, unbounded, Receiver, Sender};
 use std::thread;
+use tokio::sync::mpsc;
 
 use ffmpeg_next as ff;
 use ff::util::frame::video::Video;
@@ -8,14 +9,16 @@ use ff::util::frame::video::Video;
 use crate::ascii::AsciiFrame;
 
 pub struct WebcamCapture {
-    frame_rx: Receiver<AsciiFrame>,
+    frame_rx: mpsc::Receiver<AsciiFrame>,
+    config_tx: Sender<(u16, u16, u32)>,
     shutdown_tx: Option<Sender<()>>,
 }
 
 impl WebcamCapture {
     pub fn new(width: u32, height: u32, fps: u32) -> Result<Self> {
-        let (frame_tx, frame_rx) = bounded(10);
+        let (frame_tx, frame_rx) = mpsc::channel(10);
         let (shutdown_tx, shutdown_rx) = bounded(1);
+        let (config_tx, config_rx) = unbounded();
 
         // Initialize FFmpeg
         ff::init().context("Failed to initialize FFmpeg")?;
@@ -24,22 +27,24 @@ impl WebcamCapture {
         // Real webcam capture with FFmpeg requires complex platform-specific setup
         thread::spawn(move || {
             eprintln!("Note: Using test pattern for video. Real webcam support coming soon.");
-            generate_test_pattern(frame_tx, shutdown_rx, width as u16, height as u16, fps);
+            generate_test_pattern(frame_tx, config_rx, shutdown_rx, width as u16, height as u16, fps);
         });
 
         Ok(Self {
             frame_rx,
+            config_tx,
             shutdown_tx: Some(shutdown_tx),
         })
     }
 
-    pub fn get_frame(&self) -> Option<AsciiFrame> {
-        self.frame_rx.try_recv().ok()
+    /// Waits for the next captured frame; `None` once capture has stopped.
+    pub async fn next_frame(&mut self) -> Option<AsciiFrame> {
+        self.frame_rx.recv().await
     }
 
-    #[allow(dead_code)]
-    pub fn recv_frame(&self) -> Result<AsciiFrame> {
-        self.frame_rx.recv().map_err(|e| anyhow::anyhow!("Failed to receive frame: {}", e))
+    /// Change capture size and rate without restarting the capture thread.
+    pub fn reconfigure(&self, width: u16, height: u16, fps: u32) {
+        let _ = self.config_tx.send((width, height, fps));
     }
 }
 
@@ -53,13 +58,14 @@ impl Drop for WebcamCapture {
 
 // Simple test pattern as placeholder for real video
 fn generate_test_pattern(
-    frame_tx: Sender<AsciiFrame>,
+    frame_tx: mpsc::Sender<AsciiFrame>,
+    config_rx: Receiver<(u16, u16, u32)>,
     shutdown_rx: Receiver<()>,
-    width: u16,
-    height: u16,
+    mut width: u16,
+    mut height: u16,
     fps: u32,
 ) {
-    let frame_delay = std::time::Duration::from_millis(1000 / fps as u64);
+    let mut frame_delay = std::time::Duration::from_millis(1000 / fps.max(1) as u64);
     let mut last_frame = std::time::Instant::now();
     let mut frame_count = 0u32;
 
@@ -68,8 +74,16 @@ fn generate_test_pattern(
             break;
         }
 
-        if last_frame.elapsed() < frame_delay {
-            thread::sleep(std::time::Duration::from_millis(1));
+        while let Ok((w, h, fps)) = config_rx.try_recv() {
+            width = w;
+            height = h;
+            frame_delay = std::time::Duration::from_millis(1000 / fps.max(1) as u64);
+        }
+
+        // Sleep out the rest of the frame rather than spinning
+        let wait = frame_delay.saturating_sub(last_frame.elapsed());
+        if !wait.is_zero() {
+            thread::sleep(wait);
             continue;
         }
 
@@ -102,7 +116,7 @@ fn generate_test_pattern(
         }
 
         if let Ok(frame) = AsciiFrame::from_rgb_data(&rgb_data, width, height, false) {
-            if frame_tx.send(frame).is_err() {
+            if frame_tx.blocking_send(frame).is_err() {
                 break;
             }
         }